@@ -1,6 +1,10 @@
 use std::str::FromStr as _;
 
-use pallas::{codec::utils::Int, ledger::primitives::conway as primitives};
+use pallas::{
+    codec::{minicbor, utils::Int},
+    crypto::hash::Hasher,
+    ledger::primitives::conway as primitives,
+};
 use tx3_lang::ir;
 
 use crate::{Error, Network};
@@ -151,7 +155,16 @@ pub fn expr_into_address(
 pub fn expr_into_bytes(ir: &ir::Expression) -> Result<primitives::Bytes, Error> {
     match ir {
         ir::Expression::Bytes(x) => Ok(primitives::Bytes::from(x.clone())),
+        ir::Expression::Hash(x) => Ok(primitives::Bytes::from(x.clone())),
         ir::Expression::String(s) => Ok(primitives::Bytes::from(s.as_bytes().to_vec())),
+        ir::Expression::AdHocDirective(x) if x.name == "native_script_hash" => {
+            let hash = native_script_hash(directive_field(x, "script")?)?;
+            Ok(primitives::Bytes::from(hash.to_vec()))
+        }
+        ir::Expression::AdHocDirective(x) if x.name.starts_with("native_script_") => {
+            let script = native_script_into_cbor(&expr_into_native_script(ir)?)?;
+            Ok(primitives::Bytes::from(script))
+        }
         _ => Err(Error::CoerceError(format!("{:?}", ir), "Bytes".to_string())),
     }
 }
@@ -160,7 +173,166 @@ pub fn expr_into_hash<const SIZE: usize>(
     ir: &ir::Expression,
 ) -> Result<primitives::Hash<SIZE>, Error> {
     match ir {
-        ir::Expression::Bytes(x) => Ok(primitives::Hash::from(x.as_slice())),
+        ir::Expression::Bytes(x) if x.len() == SIZE => Ok(primitives::Hash::from(x.as_slice())),
+        ir::Expression::Hash(x) if x.len() == SIZE => Ok(primitives::Hash::from(x.as_slice())),
+        ir::Expression::AdHocDirective(x) if x.name == "native_script_hash" && SIZE == 28 => {
+            let hash = native_script_hash(directive_field(x, "script")?)?;
+            Ok(primitives::Hash::from(hash.as_slice()))
+        }
         _ => Err(Error::CoerceError(format!("{:?}", ir), "Hash".to_string())),
     }
 }
+
+pub fn address_into_key_hash(
+    address: &pallas::ledger::addresses::Address,
+) -> Result<primitives::AddrKeyhash, Error> {
+    match address {
+        pallas::ledger::addresses::Address::Shelley(x) => match x.payment() {
+            pallas::ledger::addresses::ShelleyPaymentPart::Key(x) => Ok(*x),
+            _ => Err(Error::CoerceError(
+                format!("{:?}", address),
+                "Key payment credential".to_string(),
+            )),
+        },
+        _ => Err(Error::CoerceError(
+            format!("{:?}", address),
+            "Shelley address".to_string(),
+        )),
+    }
+}
+
+pub fn expr_into_key_hash(expr: &ir::Expression) -> Result<primitives::AddrKeyhash, Error> {
+    match expr {
+        ir::Expression::Address(x) => address_into_key_hash(&bytes_into_address(x)?),
+        ir::Expression::String(x) => address_into_key_hash(&string_into_address(x)?),
+        ir::Expression::Bytes(_) | ir::Expression::Hash(_) => expr_into_hash(expr),
+        _ => Err(Error::CoerceError(
+            format!("{:?}", expr),
+            "KeyHash".to_string(),
+        )),
+    }
+}
+
+/// Looks up a field of an ad-hoc directive. Directives can come from TIR
+/// crafted by clients, so a missing field is an error rather than a bug.
+pub(crate) fn directive_field<'a>(
+    directive: &'a ir::AdHocDirective,
+    key: &str,
+) -> Result<&'a ir::Expression, Error> {
+    directive.data.get(key).ok_or_else(|| {
+        Error::CoerceError(
+            directive.name.clone(),
+            format!("directive with a '{key}' field"),
+        )
+    })
+}
+
+fn expr_into_slot(expr: &ir::Expression) -> Result<u64, Error> {
+    let slot = expr_into_number(expr)?;
+    u64::try_from(slot).map_err(|_| Error::CoerceError(slot.to_string(), "Slot".to_string()))
+}
+
+fn expr_into_native_scripts(expr: &ir::Expression) -> Result<Vec<primitives::NativeScript>, Error> {
+    match expr {
+        ir::Expression::List(x) => x.iter().map(expr_into_native_script).collect(),
+        _ => Err(Error::CoerceError(
+            format!("{:?}", expr),
+            "NativeScripts".to_string(),
+        )),
+    }
+}
+
+pub fn expr_into_native_script(expr: &ir::Expression) -> Result<primitives::NativeScript, Error> {
+    let ir::Expression::AdHocDirective(x) = expr else {
+        return Err(Error::CoerceError(
+            format!("{:?}", expr),
+            "NativeScript".to_string(),
+        ));
+    };
+
+    match x.name.as_str() {
+        "native_script_all" => Ok(primitives::NativeScript::ScriptAll(
+            expr_into_native_scripts(directive_field(x, "scripts")?)?,
+        )),
+        "native_script_any" => Ok(primitives::NativeScript::ScriptAny(
+            expr_into_native_scripts(directive_field(x, "scripts")?)?,
+        )),
+        "native_script_at_least" => {
+            let required = expr_into_number(directive_field(x, "required")?)?;
+
+            let required = u32::try_from(required)
+                .map_err(|_| Error::CoerceError(required.to_string(), "u32".to_string()))?;
+
+            Ok(primitives::NativeScript::ScriptNOfK(
+                required,
+                expr_into_native_scripts(directive_field(x, "scripts")?)?,
+            ))
+        }
+        "native_script_sig" => Ok(primitives::NativeScript::ScriptPubkey(expr_into_key_hash(
+            directive_field(x, "key")?,
+        )?)),
+        "native_script_after" => Ok(primitives::NativeScript::InvalidBefore(expr_into_slot(
+            directive_field(x, "slot")?,
+        )?)),
+        "native_script_before" => Ok(primitives::NativeScript::InvalidHereafter(expr_into_slot(
+            directive_field(x, "slot")?,
+        )?)),
+        _ => Err(Error::CoerceError(
+            format!("{:?}", expr),
+            "NativeScript".to_string(),
+        )),
+    }
+}
+
+pub fn native_script_into_cbor(script: &primitives::NativeScript) -> Result<Vec<u8>, Error> {
+    minicbor::to_vec(script)
+        .map_err(|err| Error::MappingError(format!("can't encode native script: {err}")))
+}
+
+pub fn native_script_hash(expr: &ir::Expression) -> Result<primitives::ScriptHash, Error> {
+    let script = native_script_into_cbor(&expr_into_native_script(expr)?)?;
+
+    Ok(Hasher::<224>::hash_tagged(&script, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directive(name: &str, data: Vec<(&str, ir::Expression)>) -> ir::Expression {
+        ir::Expression::AdHocDirective(Box::new(ir::AdHocDirective {
+            name: name.to_string(),
+            data: data.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+        }))
+    }
+
+    #[test]
+    fn rejects_malformed_native_scripts() {
+        let missing = directive("native_script_sig", vec![]);
+
+        assert!(matches!(
+            expr_into_native_script(&missing),
+            Err(Error::CoerceError(name, _)) if name == "native_script_sig"
+        ));
+
+        let negative = directive(
+            "native_script_after",
+            vec![("slot", ir::Expression::Number(-1))],
+        );
+
+        assert!(matches!(
+            expr_into_native_script(&negative),
+            Err(Error::CoerceError(_, target)) if target == "Slot"
+        ));
+
+        let after = directive(
+            "native_script_after",
+            vec![("slot", ir::Expression::Number(10))],
+        );
+
+        assert_eq!(
+            expr_into_native_script(&after).unwrap(),
+            primitives::NativeScript::InvalidBefore(10)
+        );
+    }
+}
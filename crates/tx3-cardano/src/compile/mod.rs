@@ -1,14 +1,18 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use pallas::{
-    codec::utils::{KeepRaw, MaybeIndefArray},
+    codec::{
+        minicbor,
+        utils::{KeepRaw, MaybeIndefArray},
+    },
+    crypto::hash::Hasher,
     ledger::{
         addresses::{Address, ShelleyPaymentPart},
         primitives::{
             conway::{self as primitives, NonEmptySet, Redeemers},
             TransactionInput,
         },
-        traverse::ComputeHash,
+        traverse::{ComputeHash, OriginalHash},
     },
 };
 
@@ -20,9 +24,30 @@ use super::*;
 
 pub(crate) mod asset_math;
 pub(crate) mod plutus_data;
+pub(crate) mod scripts;
 
 use plutus_data::{IntoData as _, TryIntoData as _};
 
+/// Chain data gathered while resolving a tx that can't be derived from the
+/// TIR alone.
+#[derive(Debug, Default, Clone)]
+pub struct Context {
+    /// Original CBOR of the datums referenced by hash from the spent inputs.
+    pub input_datums: Vec<Vec<u8>>,
+
+    /// Pure-ADA UTxOs resolved from the collateral blocks of the tx.
+    pub collateral: Vec<tx3_lang::Utxo>,
+
+    /// UTxOs referenced by the tx without being spent, such as the ones
+    /// holding the scripts of its policies.
+    pub references: Vec<tx3_lang::Utxo>,
+
+    /// Execution units of each redeemer, keyed by its tag and index, as
+    /// evaluated by running the scripts. Redeemers missing here keep the
+    /// placeholder units.
+    pub ex_units: BTreeMap<(primitives::RedeemerTag, u32), ExUnits>,
+}
+
 macro_rules! asset {
     ($policy:expr, $asset:expr, $amount:expr) => {{
         let mut aux = BTreeMap::new();
@@ -149,6 +174,24 @@ fn eval_minutxo_constructor(&self, ctr: &ir::AssetConstructor) -> Result<primiti
 }
     */
 
+fn compile_output_datum(
+    ir: &ir::Output,
+) -> Result<Option<primitives::DatumOption<'static>>, Error> {
+    match (&ir.datum, &ir.datum_hash) {
+        (Some(_), Some(_)) => Err(Error::ConflictingDatum),
+        (Some(datum), None) => {
+            let data = compile_data_expr(datum)?;
+            let option = primitives::DatumOption::Data(pallas::codec::utils::CborWrap(data.into()));
+            Ok(Some(option))
+        }
+        (None, Some(datum)) => {
+            let data = compile_data_expr(datum)?;
+            Ok(Some(primitives::DatumOption::Hash(data.compute_hash())))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
 fn compile_output_block(
     ir: &ir::Output,
     network: Network,
@@ -174,15 +217,13 @@ fn compile_output_block(
 
     let value = asset_math::aggregate_values(values);
 
-    let datum_option = ir.datum.as_ref().map(compile_data_expr).transpose()?;
+    let datum_option = compile_output_datum(ir)?;
 
     let output = primitives::TransactionOutput::PostAlonzo(
         primitives::PostAlonzoTransactionOutput {
             address: address.to_vec().into(),
             value,
-            datum_option: datum_option.map(|x| {
-                primitives::DatumOption::Data(pallas::codec::utils::CborWrap(x.into())).into()
-            }),
+            datum_option: datum_option.map(|x| x.into()),
             script_ref: None, // TODO: add script ref
         }
         .into(),
@@ -244,8 +285,8 @@ fn compile_outputs(
 fn compile_vote_delegation_certificate(
     x: &ir::AdHocDirective,
 ) -> Result<primitives::Certificate, Error> {
-    let stake = coercion::expr_into_stake_credential(&x.data["stake"])?;
-    let drep = coercion::expr_into_bytes(&x.data["drep"])?;
+    let stake = coercion::expr_into_stake_credential(coercion::directive_field(x, "stake")?)?;
+    let drep = coercion::expr_into_bytes(coercion::directive_field(x, "drep")?)?;
     let drep = primitives::DRep::Key(drep.as_slice().into());
 
     Ok(primitives::Certificate::VoteDeleg(stake, drep))
@@ -293,27 +334,220 @@ fn compile_reference_inputs(tx: &ir::Tx) -> Result<Vec<primitives::TransactionIn
     Ok(refs)
 }
 
-fn compile_collateral(tx: &ir::Tx) -> Option<NonEmptySet<TransactionInput>> {
-    tx.collateral
+/// Collateral of the tx: the inputs put at stake, the amount that would be
+/// taken on a phase-2 failure and the output returning the rest.
+struct CompiledCollateral {
+    inputs: Option<NonEmptySet<TransactionInput>>,
+    total: Option<u64>,
+    r#return: Option<primitives::TransactionOutput<'static>>,
+}
+
+pub(crate) fn utxo_ref_into_input(utxo_ref: &tx3_lang::UtxoRef) -> TransactionInput {
+    TransactionInput {
+        transaction_id: primitives::Hash::from(utxo_ref.txid.as_slice()),
+        index: utxo_ref.index as u64,
+    }
+}
+
+pub(crate) fn utxo_lovelace(utxo: &tx3_lang::Utxo) -> Result<u64, Error> {
+    let mut total = 0;
+
+    for asset in utxo.assets.iter().filter(|x| x.policy.is_none()) {
+        total += coercion::expr_into_number(&asset.amount)? as u64;
+    }
+
+    Ok(total)
+}
+
+/// Rebuilds the ledger output behind a resolved utxo, for the checks that need
+/// to look at what's being spent.
+pub(crate) fn utxo_into_output(
+    utxo: &tx3_lang::Utxo,
+) -> Result<primitives::TransactionOutput<'_>, Error> {
+    let values = utxo
+        .assets
         .iter()
-        .filter_map(|collateral| collateral.query.r#ref.as_ref())
-        .find_map(|r#ref| match r#ref {
-            ir::Expression::UtxoRefs(refs) => refs.first().map(|utxo_ref| {
-                let index = utxo_ref.index;
-                let hash = primitives::Hash::from(utxo_ref.txid.as_slice());
-
-                NonEmptySet::from_vec(vec![TransactionInput {
-                    transaction_id: hash,
-                    index: index as u64,
-                }])
-            }),
-            _ => None,
-        })
-        .flatten()
+        .map(compile_value)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let datum_option = match (&utxo.datum, &utxo.datum_hash) {
+        (Some(datum), _) => {
+            let data = compile_data_expr(datum)?;
+            let option = primitives::DatumOption::Data(pallas::codec::utils::CborWrap(data.into()));
+            Some(option)
+        }
+        (None, Some(hash)) => Some(primitives::DatumOption::Hash(primitives::Hash::from(
+            hash.as_slice(),
+        ))),
+        (None, None) => None,
+    };
+
+    let script_ref = match &utxo.script {
+        Some(ir::Expression::Bytes(bytes)) => {
+            let script: primitives::ScriptRef = minicbor::decode(bytes)
+                .map_err(|err| Error::MappingError(format!("can't decode script ref: {err}")))?;
+
+            Some(pallas::codec::utils::CborWrap(script))
+        }
+        Some(x) => {
+            return Err(Error::CoerceError(
+                format!("{x:?}"),
+                "ScriptRef".to_string(),
+            ))
+        }
+        None => None,
+    };
+
+    let output = primitives::TransactionOutput::PostAlonzo(
+        primitives::PostAlonzoTransactionOutput {
+            address: utxo.address.clone().into(),
+            value: asset_math::aggregate_values(values),
+            datum_option: datum_option.map(|x| x.into()),
+            script_ref,
+        }
+        .into(),
+    );
+
+    Ok(output)
+}
+
+pub(crate) fn min_lovelace(
+    output: &primitives::TransactionOutput,
+    pparams: &PParams,
+) -> Result<u64, Error> {
+    let serialized = minicbor::to_vec(output)
+        .map_err(|err| Error::MappingError(format!("can't encode output: {err}")))?;
+
+    Ok((160 + serialized.len() as u64) * pparams.coins_per_utxo_byte)
+}
+
+fn compile_collateral_refs(tx: &ir::Tx) -> Result<Vec<TransactionInput>, Error> {
+    let mut refs = vec![];
+
+    for r#ref in tx.collateral.iter().filter_map(|x| x.query.r#ref.as_ref()) {
+        for utxo_ref in coercion::expr_into_utxo_refs(r#ref)? {
+            let input = utxo_ref_into_input(&utxo_ref);
+
+            if !refs.contains(&input) {
+                refs.push(input);
+            }
+        }
+    }
+
+    Ok(refs)
 }
 
-fn compile_required_signers(tx: &ir::Tx) -> Result<Option<primitives::RequiredSigners>, Error> {
-    let mut hashes = Vec::new();
+/// Computes the collateral required by the tx, either as a percentage of its
+/// fees or as the explicit min amount of the collateral blocks.
+fn required_collateral(tx: &ir::Tx, pparams: &PParams) -> Result<u64, Error> {
+    let fee = coercion::expr_into_number(&tx.fees)? as u64;
+    let mut required = (fee * pparams.collateral_percentage).div_ceil(100);
+
+    let min_amounts = tx
+        .collateral
+        .iter()
+        .filter_map(|x| x.query.min_amount.as_ref());
+
+    for min_amount in min_amounts {
+        let assets = coercion::expr_into_assets(min_amount)?;
+
+        for asset in assets.iter().filter(|x| x.policy.is_none()) {
+            required = required.max(coercion::expr_into_number(&asset.amount)? as u64);
+        }
+    }
+
+    Ok(required)
+}
+
+fn compile_collateral(
+    tx: &ir::Tx,
+    pparams: &PParams,
+    context: &Context,
+) -> Result<CompiledCollateral, Error> {
+    // without resolved utxos there's no way to know their value, we can only
+    // reference the explicit ones
+    if context.collateral.is_empty() {
+        return Ok(CompiledCollateral {
+            inputs: NonEmptySet::from_vec(compile_collateral_refs(tx)?),
+            total: None,
+            r#return: None,
+        });
+    }
+
+    let required = required_collateral(tx, pparams)?;
+
+    let mut candidates = context
+        .collateral
+        .iter()
+        .map(|x| Ok((x, utxo_lovelace(x)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // bigger utxos first, so that we need as few inputs as possible
+    candidates.sort_by(|(a, x), (b, y)| {
+        y.cmp(x)
+            .then_with(|| a.r#ref.txid.cmp(&b.r#ref.txid))
+            .then_with(|| a.r#ref.index.cmp(&b.r#ref.index))
+    });
+
+    let mut selected = vec![];
+    let mut available = 0;
+
+    for (utxo, lovelace) in candidates {
+        if !selected.is_empty() && available >= required {
+            break;
+        }
+
+        if selected.len() as u64 >= pparams.max_collateral_inputs {
+            break;
+        }
+
+        selected.push(utxo);
+        available += lovelace;
+    }
+
+    if selected.is_empty() || available < required {
+        return Err(Error::InsufficientCollateral(required));
+    }
+
+    let inputs = selected
+        .iter()
+        .map(|x| utxo_ref_into_input(&x.r#ref))
+        .collect();
+
+    let address = coercion::bytes_into_address(&selected[0].address)?;
+
+    let r#return = primitives::TransactionOutput::PostAlonzo(
+        primitives::PostAlonzoTransactionOutput {
+            address: address.to_vec().into(),
+            value: primitives::Value::Coin(available - required),
+            datum_option: None,
+            script_ref: None,
+        }
+        .into(),
+    );
+
+    // if the change is too small to be returned, the whole collateral is at
+    // stake
+    if available - required < min_lovelace(&r#return, pparams)? {
+        return Ok(CompiledCollateral {
+            inputs: NonEmptySet::from_vec(inputs),
+            total: Some(available),
+            r#return: None,
+        });
+    }
+
+    Ok(CompiledCollateral {
+        inputs: NonEmptySet::from_vec(inputs),
+        total: Some(required),
+        r#return: Some(r#return),
+    })
+}
+
+fn compile_required_signers(
+    tx: &ir::Tx,
+    native: &scripts::NativeRequirements,
+) -> Result<Option<primitives::RequiredSigners>, Error> {
+    let mut hashes = native.signers.clone();
     let Some(signers) = &tx.signers else {
         return Ok(primitives::RequiredSigners::from_vec(hashes));
     };
@@ -336,11 +570,17 @@ fn compile_required_signers(tx: &ir::Tx) -> Result<Option<primitives::RequiredSi
                     ));
                 };
         
-                hashes.push(*key);
+                if !hashes.contains(key) {
+                    hashes.push(*key);
+                }
             },
             ir::Expression::Bytes(b) => {
                 let bytes = primitives::Bytes::from(b.clone());
-                hashes.push(primitives::AddrKeyhash::from(bytes.as_slice()));
+                let key = primitives::AddrKeyhash::from(bytes.as_slice());
+
+                if !hashes.contains(&key) {
+                    hashes.push(key);
+                }
             },
             _ => {
                 return Err(Error::CoerceError(
@@ -354,7 +594,10 @@ fn compile_required_signers(tx: &ir::Tx) -> Result<Option<primitives::RequiredSi
     Ok(primitives::RequiredSigners::from_vec(hashes))
 }
 
-fn compile_validity(validity: Option<&ir::Validity>) -> Result<(Option<u64>, Option<u64>), Error> {
+fn compile_validity(
+    validity: Option<&ir::Validity>,
+    native: &scripts::NativeRequirements,
+) -> Result<(Option<u64>, Option<u64>), Error> {
     let since = validity
         .and_then(|v| v.since.as_ref())
         .map(|expr| coercion::expr_into_number(expr).map(|n| n as u64))
@@ -365,14 +608,26 @@ fn compile_validity(validity: Option<&ir::Validity>) -> Result<(Option<u64>, Opt
         .map(|expr| coercion::expr_into_number(expr).map(|n| n as u64))
         .transpose()?;
 
+    // native scripts can only narrow the validity interval of the tx
+    let since = since.max(native.since);
+
+    let until = match (until, native.until) {
+        (Some(x), Some(y)) => Some(x.min(y)),
+        (x, y) => x.or(y),
+    };
+
     Ok((since, until))
 }
 
 fn compile_tx_body(
     tx: &ir::Tx,
-    network: Network,
+    pparams: &PParams,
+    context: &Context,
 ) -> Result<primitives::TransactionBody<'static>, Error> {
-    let (since, until) = compile_validity(tx.validity.as_ref())?;
+    let network = pparams.network;
+    let native = scripts::native_requirements(tx)?;
+    let collateral = compile_collateral(tx, pparams, context)?;
+    let (since, until) = compile_validity(tx.validity.as_ref(), &native)?;
 
     let out = primitives::TransactionBody {
         inputs: compile_inputs(tx)?.into(),
@@ -387,10 +642,10 @@ fn compile_tx_body(
         withdrawals: None,
         auxiliary_data_hash: None,
         script_data_hash: None,
-        collateral: compile_collateral(tx),
-        required_signers: compile_required_signers(tx)?,
-        collateral_return: None,
-        total_collateral: None,
+        collateral: collateral.inputs,
+        required_signers: compile_required_signers(tx, &native)?,
+        collateral_return: collateral.r#return,
+        total_collateral: collateral.total,
         voting_procedures: None,
         proposal_procedures: None,
         treasury_value: None,
@@ -528,9 +783,17 @@ fn compile_mint_redeemers(
     tx: &ir::Tx,
     compiled_body: &primitives::TransactionBody,
 ) -> Result<Vec<primitives::Redeemer>, Error> {
+    // native scripts don't take redeemers
+    let is_native = |mint: &&ir::Mint| {
+        mint.policy
+            .as_ref()
+            .is_some_and(|x| x.language == Some(ir::ScriptLanguage::Native))
+    };
+
     let redeemers = tx
         .mints
         .iter()
+        .filter(|mint| !is_native(mint))
         .map(|mint| compile_mint_redeemer(mint, compiled_body))
         .collect::<Result<Vec<_>, _>>()?;
 
@@ -540,12 +803,22 @@ fn compile_mint_redeemers(
 fn compile_redeemers(
     tx: &ir::Tx,
     compiled_body: &primitives::TransactionBody,
+    context: &Context,
 ) -> Result<Option<Redeemers>, Error> {
     let spend_redeemers = compile_spend_redeemers(tx, compiled_body)?;
     let mint_redeemers = compile_mint_redeemers(tx, compiled_body)?;
 
     // TODO: chain other redeemers
-    let redeemers: Vec<_> = spend_redeemers.into_iter().chain(mint_redeemers).collect();
+    let mut redeemers: Vec<_> = spend_redeemers.into_iter().chain(mint_redeemers).collect();
+
+    for redeemer in redeemers.iter_mut() {
+        if let Some(units) = context.ex_units.get(&(redeemer.tag, redeemer.index)) {
+            redeemer.ex_units = primitives::ExUnits {
+                mem: units.mem,
+                steps: units.steps,
+            };
+        }
+    }
 
     if redeemers.is_empty() {
         Ok(None)
@@ -556,52 +829,186 @@ fn compile_redeemers(
     }
 }
 
+fn decode_input_datum(cbor: &[u8]) -> Result<KeepRaw<'static, primitives::PlutusData>, Error> {
+    let datum: KeepRaw<primitives::PlutusData> = pallas::codec::minicbor::decode(cbor)
+        .map_err(|err| Error::MappingError(format!("invalid datum cbor: {err}")))?;
+
+    Ok(datum.to_owned())
+}
+
+fn compile_witness_datums(
+    tx: &ir::Tx,
+    context: &Context,
+) -> Result<Vec<KeepRaw<'static, primitives::PlutusData>>, Error> {
+    // the ledger rejects duplicated witness datums, so we keep only the first
+    // occurrence of each hash
+    let mut hashes = vec![];
+    let mut datums = vec![];
+
+    for datum in tx.outputs.iter().filter_map(|x| x.datum_hash.as_ref()) {
+        let datum = compile_data_expr(datum)?;
+
+        if !hashes.contains(&datum.compute_hash()) {
+            hashes.push(datum.compute_hash());
+            datums.push(KeepRaw::from(datum));
+        }
+    }
+
+    for cbor in context.input_datums.iter() {
+        let datum = decode_input_datum(cbor)?;
+
+        if !hashes.contains(&datum.original_hash()) {
+            hashes.push(datum.original_hash());
+            datums.push(datum);
+        }
+    }
+
+    Ok(datums)
+}
+
 fn compile_witness_set(
     tx: &ir::Tx,
     compiled_body: &primitives::TransactionBody,
+    context: &Context,
 ) -> Result<primitives::WitnessSet<'static>, Error> {
+    let scripts = scripts::compile_embedded_scripts(tx)?;
+
     let witness_set = primitives::WitnessSet {
-        redeemer: compile_redeemers(tx, compiled_body)?.map(|x| x.into()),
+        redeemer: compile_redeemers(tx, compiled_body, context)?.map(|x| x.into()),
         vkeywitness: None,
-        native_script: None,
+        native_script: NonEmptySet::from_vec(scripts.native),
         bootstrap_witness: None,
-        plutus_v1_script: None,
-        plutus_data: None,
-        plutus_v2_script: None,
-        plutus_v3_script: None,
+        plutus_v1_script: NonEmptySet::from_vec(scripts.plutus_v1),
+        plutus_data: NonEmptySet::from_vec(compile_witness_datums(tx, context)?),
+        plutus_v2_script: NonEmptySet::from_vec(scripts.plutus_v2),
+        plutus_v3_script: NonEmptySet::from_vec(scripts.plutus_v3),
     };
 
     Ok(witness_set)
 }
 
-fn infer_plutus_version(_transaction_body: &primitives::TransactionBody) -> PlutusVersion {
-    // TODO: infer plutus version from existing scripts
-    1
+fn script_data_encode_error(err: impl std::fmt::Display) -> Error {
+    Error::MappingError(format!("can't encode script data: {err}"))
+}
+
+fn encode_language_view(
+    version: PlutusVersion,
+    cost_model: &CostModel,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    // plutus v1 keeps the legacy encoding where both the key and the cost
+    // model (as an indefinite array) are wrapped as bytes
+    if version == 0 {
+        let mut inner = minicbor::Encoder::new(vec![]);
+        inner.begin_array().map_err(script_data_encode_error)?;
+
+        for value in cost_model {
+            inner.encode(value).map_err(script_data_encode_error)?;
+        }
+
+        inner.end().map_err(script_data_encode_error)?;
+
+        let key = minicbor::to_vec(primitives::Bytes::from(vec![0u8]));
+        let value = minicbor::to_vec(primitives::Bytes::from(inner.into_writer()));
+
+        return Ok((
+            key.map_err(script_data_encode_error)?,
+            value.map_err(script_data_encode_error)?,
+        ));
+    }
+
+    let key = minicbor::to_vec(version).map_err(script_data_encode_error)?;
+    let value = minicbor::to_vec(cost_model).map_err(script_data_encode_error)?;
+
+    Ok((key, value))
+}
+
+/// Encodes the language views of the given plutus versions as a canonical
+/// CBOR map, sorting entries by the length and then the bytes of their keys.
+fn encode_language_views(
+    versions: &BTreeSet<PlutusVersion>,
+    pparams: &PParams,
+) -> Result<Vec<u8>, Error> {
+    let mut entries = vec![];
+
+    for version in versions {
+        let cost_model = pparams
+            .cost_models
+            .get(version)
+            .filter(|x| !x.is_empty())
+            .ok_or(Error::MissingCostModel(*version))?;
+
+        entries.push(encode_language_view(*version, cost_model)?);
+    }
+
+    entries.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+    let mut out = minicbor::Encoder::new(vec![]);
+    out.map(entries.len() as u64)
+        .map_err(script_data_encode_error)?;
+
+    let mut out = out.into_writer();
+
+    for (key, value) in entries {
+        out.extend(key);
+        out.extend(value);
+    }
+
+    Ok(out)
 }
 
+/// Computes the script integrity hash of the tx as defined by the Conway
+/// spec: the hash of the redeemers, the witness datums and the language views
+/// of every plutus version used by the tx.
 fn compute_script_data_hash(
-    body: &primitives::TransactionBody,
+    tx: &ir::Tx,
     witness_set: &primitives::WitnessSet,
     pparams: &PParams,
-) -> Option<primitives::Hash<32>> {
-    let version = infer_plutus_version(body);
+    context: &Context,
+) -> Result<Option<primitives::Hash<32>>, Error> {
+    let redeemers = witness_set.redeemer.as_ref();
+    let datums = witness_set.plutus_data.as_ref();
 
-    let cost_model = pparams.cost_models.get(&version).unwrap();
+    if redeemers.is_none() && datums.is_none() {
+        return Ok(None);
+    }
 
-    let language_view = primitives::LanguageView(version, cost_model.clone());
+    let mut buf = vec![];
 
-    let data = primitives::ScriptData::build_for(witness_set, language_view);
+    match redeemers {
+        Some(redeemers) => {
+            buf.extend(minicbor::to_vec(redeemers).map_err(script_data_encode_error)?)
+        }
+        // no redeemers means an empty map
+        None => buf.push(0xa0),
+    }
 
-    data.map(|x| x.hash())
+    if let Some(datums) = datums {
+        buf.extend(minicbor::to_vec(datums).map_err(script_data_encode_error)?);
+    }
+
+    match redeemers {
+        Some(_) => {
+            let versions = scripts::infer_plutus_versions(tx, &context.references)?;
+            buf.extend(encode_language_views(&versions, pparams)?);
+        }
+        // without redeemers no script is executed, language views are empty
+        None => buf.push(0xa0),
+    }
+
+    Ok(Some(Hasher::<256>::hash(&buf)))
 }
 
-pub fn compile_tx(tx: &ir::Tx, pparams: &PParams) -> Result<primitives::Tx<'static>, Error> {
-    let mut transaction_body = compile_tx_body(tx, pparams.network)?;
-    let transaction_witness_set = compile_witness_set(tx, &transaction_body)?;
+pub fn compile_tx(
+    tx: &ir::Tx,
+    pparams: &PParams,
+    context: &Context,
+) -> Result<primitives::Tx<'static>, Error> {
+    let mut transaction_body = compile_tx_body(tx, pparams, context)?;
+    let transaction_witness_set = compile_witness_set(tx, &transaction_body, context)?;
     let auxiliary_data = compile_auxiliary_data(tx)?;
 
     transaction_body.script_data_hash =
-        compute_script_data_hash(&transaction_body, &transaction_witness_set, pparams);
+        compute_script_data_hash(tx, &transaction_witness_set, pparams, context)?;
 
     transaction_body.auxiliary_data_hash = auxiliary_data
         .as_ref()
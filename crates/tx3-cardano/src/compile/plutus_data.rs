@@ -142,3 +142,118 @@ impl TryIntoData for ir::Expression {
         }
     }
 }
+
+pub trait TryIntoExpr {
+    fn try_as_expr(&self) -> Result<ir::Expression, super::Error>;
+}
+
+fn constr_index(constr: &Constr<PlutusData>) -> u64 {
+    match constr.tag {
+        121..=127 => constr.tag - 121,
+        1280..=1400 => constr.tag - 1280 + 7,
+        _ => constr.any_constructor.unwrap_or_default(),
+    }
+}
+
+fn big_uint_into_number(bytes: &BoundedBytes) -> Result<i128, super::Error> {
+    // anything wider than 15 bytes could overflow a signed 128-bit number
+    if bytes.len() > 15 {
+        return Err(super::Error::CoerceError(
+            hex::encode(bytes.as_slice()),
+            "Number".to_string(),
+        ));
+    }
+
+    Ok(bytes.iter().fold(0, |acc, x| (acc << 8) | *x as i128))
+}
+
+impl TryIntoExpr for PlutusData {
+    fn try_as_expr(&self) -> Result<ir::Expression, super::Error> {
+        match self {
+            PlutusData::Constr(x) => {
+                let fields = x
+                    .fields
+                    .iter()
+                    .map(TryIntoExpr::try_as_expr)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(ir::Expression::Struct(ir::StructExpr {
+                    constructor: constr_index(x) as usize,
+                    fields,
+                }))
+            }
+            PlutusData::Map(x) => {
+                let pairs = x
+                    .iter()
+                    .map(|(k, v)| {
+                        Ok(ir::Expression::Tuple(Box::new((
+                            k.try_as_expr()?,
+                            v.try_as_expr()?,
+                        ))))
+                    })
+                    .collect::<Result<Vec<_>, super::Error>>()?;
+
+                Ok(ir::Expression::List(pairs))
+            }
+            PlutusData::BigInt(BigInt::Int(x)) => Ok(ir::Expression::Number(i128::from(*x))),
+            PlutusData::BigInt(BigInt::BigUInt(x)) => {
+                Ok(ir::Expression::Number(big_uint_into_number(x)?))
+            }
+            PlutusData::BigInt(BigInt::BigNInt(x)) => {
+                Ok(ir::Expression::Number(-1 - big_uint_into_number(x)?))
+            }
+            PlutusData::BoundedBytes(x) => Ok(ir::Expression::Bytes(x.to_vec())),
+            PlutusData::Array(x) => {
+                let items = x
+                    .iter()
+                    .map(TryIntoExpr::try_as_expr)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(ir::Expression::List(items))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_roundtrips_through_expr() {
+        let data = constr(
+            1,
+            vec![
+                42u64.as_data(),
+                vec![1u8, 2, 3].as_data(),
+                PlutusData::Array(MaybeIndefArray::Def(vec![constr(0, vec![])])),
+            ],
+        );
+
+        let expr = data.try_as_expr().unwrap();
+
+        assert_eq!(
+            expr,
+            ir::Expression::Struct(ir::StructExpr {
+                constructor: 1,
+                fields: vec![
+                    ir::Expression::Number(42),
+                    ir::Expression::Bytes(vec![1, 2, 3]),
+                    ir::Expression::List(vec![ir::Expression::Struct(ir::StructExpr {
+                        constructor: 0,
+                        fields: vec![],
+                    })]),
+                ],
+            })
+        );
+
+        assert_eq!(expr.try_as_data().unwrap(), data);
+    }
+
+    #[test]
+    fn big_ints_into_numbers() {
+        let data = PlutusData::BigInt(BigInt::BigNInt(BoundedBytes::from(vec![0x01, 0x00])));
+
+        assert_eq!(data.try_as_expr().unwrap(), ir::Expression::Number(-257));
+    }
+}
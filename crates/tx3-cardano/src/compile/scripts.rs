@@ -0,0 +1,498 @@
+use std::collections::BTreeSet;
+
+use pallas::{
+    codec::{minicbor, utils::KeepRaw},
+    crypto::hash::Hasher,
+    ledger::{primitives::conway as primitives, traverse::ComputeHash as _},
+};
+
+use tracing::warn;
+use tx3_lang::ir;
+
+use crate::{coercion, Error, PlutusVersion};
+
+/// Language assumed for the scripts executed by the tx when theirs can't be
+/// determined, like the ones of policies declared only by their hash (eg:
+/// `policy X = 0x...;`), which has no way to state a language.
+const DEFAULT_PLUTUS_LANGUAGE: ir::ScriptLanguage = ir::ScriptLanguage::PlutusV3;
+
+const ALL_LANGUAGES: [ir::ScriptLanguage; 4] = [
+    ir::ScriptLanguage::Native,
+    ir::ScriptLanguage::PlutusV1,
+    ir::ScriptLanguage::PlutusV2,
+    ir::ScriptLanguage::PlutusV3,
+];
+
+/// Scripts that need to be included in the witness set, grouped by language.
+#[derive(Debug, Default)]
+pub(crate) struct ScriptWitnesses {
+    pub native: Vec<KeepRaw<'static, primitives::NativeScript>>,
+    pub plutus_v1: Vec<primitives::PlutusScript<1>>,
+    pub plutus_v2: Vec<primitives::PlutusScript<2>>,
+    pub plutus_v3: Vec<primitives::PlutusScript<3>>,
+    hashes: Vec<primitives::ScriptHash>,
+}
+
+impl ScriptWitnesses {
+    fn push(&mut self, language: ir::ScriptLanguage, script: &[u8]) -> Result<(), Error> {
+        let hash = script_hash(language, script)?;
+
+        if self.hashes.contains(&hash) {
+            return Ok(());
+        }
+
+        match language {
+            ir::ScriptLanguage::Native => self.native.push(decode_native_script(script)?),
+            ir::ScriptLanguage::PlutusV1 => self
+                .plutus_v1
+                .push(primitives::PlutusScript(script.to_vec().into())),
+            ir::ScriptLanguage::PlutusV2 => self
+                .plutus_v2
+                .push(primitives::PlutusScript(script.to_vec().into())),
+            ir::ScriptLanguage::PlutusV3 => self
+                .plutus_v3
+                .push(primitives::PlutusScript(script.to_vec().into())),
+        }
+
+        self.hashes.push(hash);
+
+        Ok(())
+    }
+}
+
+fn decode_native_script(
+    script: &[u8],
+) -> Result<KeepRaw<'static, primitives::NativeScript>, Error> {
+    let script: KeepRaw<primitives::NativeScript> = minicbor::decode(script)
+        .map_err(|err| Error::MappingError(format!("invalid native script cbor: {err}")))?;
+
+    Ok(script.to_owned())
+}
+
+/// Computes the hash of a script as it would be seen on-chain.
+pub(crate) fn script_hash(
+    language: ir::ScriptLanguage,
+    script: &[u8],
+) -> Result<primitives::ScriptHash, Error> {
+    let hash = match language {
+        ir::ScriptLanguage::Native => {
+            let script = decode_native_script(script)?;
+            Hasher::<224>::hash_tagged(script.raw_cbor(), 0)
+        }
+        ir::ScriptLanguage::PlutusV1 => {
+            primitives::PlutusScript::<1>(script.to_vec().into()).compute_hash()
+        }
+        ir::ScriptLanguage::PlutusV2 => {
+            primitives::PlutusScript::<2>(script.to_vec().into()).compute_hash()
+        }
+        ir::ScriptLanguage::PlutusV3 => {
+            primitives::PlutusScript::<3>(script.to_vec().into()).compute_hash()
+        }
+    };
+
+    Ok(hash)
+}
+
+/// Figures out the language of a script by finding the one that yields the
+/// expected hash.
+fn infer_language(script: &[u8], expected: &primitives::ScriptHash) -> Option<ir::ScriptLanguage> {
+    ALL_LANGUAGES
+        .into_iter()
+        .find(|x| script_hash(*x, script).is_ok_and(|hash| hash == *expected))
+}
+
+pub(crate) fn tx_policies(tx: &ir::Tx) -> impl Iterator<Item = &ir::PolicyExpr> {
+    let inputs = tx.inputs.iter().filter_map(|x| x.policy.as_ref());
+    let mints = tx.mints.iter().filter_map(|x| x.policy.as_ref());
+
+    inputs.chain(mints)
+}
+
+fn embedded_script(policy: &ir::PolicyExpr) -> Option<&ir::Expression> {
+    match &policy.script {
+        Some(ir::ScriptSource::Embedded(ir::Expression::UtxoRefs(_))) => None,
+        Some(ir::ScriptSource::Embedded(x)) => Some(x),
+        _ => None,
+    }
+}
+
+fn compile_embedded_script(
+    policy: &ir::PolicyExpr,
+    script: &ir::Expression,
+) -> Result<(ir::ScriptLanguage, Vec<u8>), Error> {
+    let script = coercion::expr_into_bytes(script)?.to_vec();
+    let expected = coercion::expr_into_hash::<28>(&policy.hash)?;
+
+    let language = match policy.language {
+        Some(x) => x,
+        None => infer_language(&script, &expected)
+            .ok_or_else(|| Error::UnknownScriptLanguage(format!("policy '{}'", policy.name)))?,
+    };
+
+    if script_hash(language, &script)? != expected {
+        return Err(Error::ScriptHashMismatch(policy.name.clone()));
+    }
+
+    Ok((language, script))
+}
+
+/// Decodes a script as it's stored in a reference UTxO (a CBOR `ScriptRef`).
+fn decode_script_ref(bytes: &[u8]) -> Option<(ir::ScriptLanguage, Vec<u8>)> {
+    let script: primitives::ScriptRef = minicbor::decode(bytes).ok()?;
+
+    let out = match script {
+        primitives::ScriptRef::NativeScript(x) => {
+            (ir::ScriptLanguage::Native, x.raw_cbor().to_vec())
+        }
+        primitives::ScriptRef::PlutusV1Script(x) => (ir::ScriptLanguage::PlutusV1, x.0.to_vec()),
+        primitives::ScriptRef::PlutusV2Script(x) => (ir::ScriptLanguage::PlutusV2, x.0.to_vec()),
+        primitives::ScriptRef::PlutusV3Script(x) => (ir::ScriptLanguage::PlutusV3, x.0.to_vec()),
+    };
+
+    Some(out)
+}
+
+/// Finds the language of a reference script by looking up the UTxO that holds
+/// it among the ones read from the ledger.
+fn referenced_language(
+    r#ref: &ir::Expression,
+    references: &[tx3_lang::Utxo],
+) -> Option<ir::ScriptLanguage> {
+    let ir::Expression::UtxoRefs(refs) = r#ref else {
+        return None;
+    };
+
+    references
+        .iter()
+        .filter(|x| refs.contains(&x.r#ref))
+        .find_map(|x| match &x.script {
+            Some(ir::Expression::Bytes(script)) => decode_script_ref(script),
+            _ => None,
+        })
+        .map(|(language, _)| language)
+}
+
+/// Determines the language of the script behind a policy, either because
+/// it's declared, embedded or available from the resolved reference UTxO.
+fn policy_language(
+    policy: &ir::PolicyExpr,
+    references: &[tx3_lang::Utxo],
+) -> Result<Option<ir::ScriptLanguage>, Error> {
+    if let Some(language) = policy.language {
+        return Ok(Some(language));
+    }
+
+    match &policy.script {
+        Some(ir::ScriptSource::Embedded(x @ ir::Expression::UtxoRefs(_))) => {
+            Ok(referenced_language(x, references))
+        }
+        Some(ir::ScriptSource::Embedded(x)) => {
+            let (language, _) = compile_embedded_script(policy, x)?;
+            Ok(Some(language))
+        }
+        Some(ir::ScriptSource::UtxoRef {
+            source: Some(source),
+            ..
+        }) => {
+            let source = coercion::expr_into_bytes(source)?;
+
+            if let Some((language, _)) = decode_script_ref(&source) {
+                return Ok(Some(language));
+            }
+
+            let expected = coercion::expr_into_hash::<28>(&policy.hash)?;
+
+            Ok(infer_language(&source, &expected))
+        }
+        Some(ir::ScriptSource::UtxoRef {
+            r#ref,
+            source: None,
+        }) => Ok(referenced_language(r#ref, references)),
+        None => Ok(None),
+    }
+}
+
+/// Maps a script language into the plutus version used as key for cost
+/// models and language views. Native scripts have no version.
+pub(crate) fn plutus_version(language: ir::ScriptLanguage) -> Option<PlutusVersion> {
+    match language {
+        ir::ScriptLanguage::Native => None,
+        ir::ScriptLanguage::PlutusV1 => Some(0),
+        ir::ScriptLanguage::PlutusV2 => Some(1),
+        ir::ScriptLanguage::PlutusV3 => Some(2),
+    }
+}
+
+/// Finds the plutus versions of the scripts executed by the tx, which are the
+/// ones behind the inputs spent with a redeemer and behind the mints. The TIR
+/// has no notion of withdrawals yet, so there's nothing to inspect there.
+///
+/// Scripts of unknown language are assumed to be [`DEFAULT_PLUTUS_LANGUAGE`].
+/// A wrong guess yields the wrong language views and the node rejects the tx
+/// with a script integrity error, so a warning is logged whenever it's used.
+pub(crate) fn infer_plutus_versions(
+    tx: &ir::Tx,
+    references: &[tx3_lang::Utxo],
+) -> Result<BTreeSet<PlutusVersion>, Error> {
+    let spends = tx
+        .inputs
+        .iter()
+        .filter(|x| x.redeemer.is_some())
+        .map(|x| (x.policy.as_ref(), format!("input '{}'", x.name)));
+
+    let mints = tx
+        .mints
+        .iter()
+        .map(|x| (x.policy.as_ref(), "a mint".to_string()));
+
+    let mut out = BTreeSet::new();
+
+    for (policy, owner) in spends.chain(mints) {
+        let language = match policy {
+            Some(policy) => policy_language(policy, references)?,
+            None => None,
+        };
+
+        let language = language.unwrap_or_else(|| {
+            warn!(
+                owner,
+                "can't determine the script language, assuming {DEFAULT_PLUTUS_LANGUAGE:?}"
+            );
+
+            DEFAULT_PLUTUS_LANGUAGE
+        });
+
+        out.extend(plutus_version(language));
+    }
+
+    Ok(out)
+}
+
+/// Collects the scripts embedded in the policies of the tx.
+pub(crate) fn compile_embedded_scripts(tx: &ir::Tx) -> Result<ScriptWitnesses, Error> {
+    let mut out = ScriptWitnesses::default();
+
+    for policy in tx_policies(tx) {
+        if let Some(script) = embedded_script(policy) {
+            let (language, script) = compile_embedded_script(policy, script)?;
+            out.push(language, &script)?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Constraints that the native scripts executed by the tx impose on it.
+#[derive(Debug, Default)]
+pub(crate) struct NativeRequirements {
+    pub signers: Vec<primitives::AddrKeyhash>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl NativeRequirements {
+    fn collect(&mut self, script: &primitives::NativeScript) {
+        match script {
+            primitives::NativeScript::ScriptPubkey(x) if !self.signers.contains(x) => {
+                self.signers.push(*x);
+            }
+            primitives::NativeScript::ScriptAll(x) => x.iter().for_each(|x| self.collect(x)),
+            primitives::NativeScript::ScriptAny(x) if x.len() == 1 => self.collect(&x[0]),
+            primitives::NativeScript::ScriptNOfK(n, x) if *n as usize >= x.len() => {
+                x.iter().for_each(|x| self.collect(x))
+            }
+            primitives::NativeScript::InvalidBefore(slot) => {
+                self.since = Some(self.since.map_or(*slot, |x| x.max(*slot)));
+            }
+            primitives::NativeScript::InvalidHereafter(slot) => {
+                self.until = Some(self.until.map_or(*slot, |x| x.min(*slot)));
+            }
+            // when the tx can choose which branch to satisfy, there's no
+            // specific requirement we can infer
+            _ => (),
+        }
+    }
+}
+
+/// Infers the signers and validity bounds required by the native scripts
+/// embedded in the policies of the tx.
+pub(crate) fn native_requirements(tx: &ir::Tx) -> Result<NativeRequirements, Error> {
+    let mut out = NativeRequirements::default();
+
+    for policy in tx_policies(tx) {
+        let Some(script) = embedded_script(policy) else {
+            continue;
+        };
+
+        let (language, script) = compile_embedded_script(policy, script)?;
+
+        if language == ir::ScriptLanguage::Native {
+            let script = decode_native_script(&script)?;
+            out.collect(&script);
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a plutus v3 script that always succeeds
+    const ALWAYS_TRUE: &str = "46450101002499";
+
+    fn policy(
+        script: &[u8],
+        hash: primitives::ScriptHash,
+        language: Option<ir::ScriptLanguage>,
+    ) -> ir::PolicyExpr {
+        ir::PolicyExpr {
+            name: "MyPolicy".to_string(),
+            hash: ir::Expression::Hash(hash.to_vec()),
+            script: Some(ir::ScriptSource::Embedded(ir::Expression::Bytes(
+                script.to_vec(),
+            ))),
+            language,
+        }
+    }
+
+    #[test]
+    fn infers_language_from_hash() {
+        let script = hex::decode(ALWAYS_TRUE).unwrap();
+        let hash = script_hash(ir::ScriptLanguage::PlutusV3, &script).unwrap();
+        let policy = policy(&script, hash, None);
+
+        let (language, _) =
+            compile_embedded_script(&policy, &ir::Expression::Bytes(script)).unwrap();
+
+        assert_eq!(language, ir::ScriptLanguage::PlutusV3);
+    }
+
+    #[test]
+    fn rejects_mismatched_language() {
+        let script = hex::decode(ALWAYS_TRUE).unwrap();
+        let hash = script_hash(ir::ScriptLanguage::PlutusV3, &script).unwrap();
+        let policy = policy(&script, hash, Some(ir::ScriptLanguage::PlutusV2));
+
+        let result = compile_embedded_script(&policy, &ir::Expression::Bytes(script));
+
+        assert!(matches!(result, Err(Error::ScriptHashMismatch(_))));
+    }
+
+    #[test]
+    fn infers_plutus_versions_of_policies() {
+        let script = hex::decode(ALWAYS_TRUE).unwrap();
+        let hash = script_hash(ir::ScriptLanguage::PlutusV3, &script).unwrap();
+
+        let tx = ir::Tx {
+            fees: ir::Expression::None,
+            references: vec![],
+            inputs: vec![],
+            outputs: vec![],
+            validity: None,
+            mints: vec![ir::Mint {
+                amount: None,
+                redeemer: None,
+                policy: Some(policy(&script, hash, None)),
+            }],
+            adhoc: vec![],
+            collateral: vec![],
+            signers: None,
+            metadata: vec![],
+        };
+
+        let versions = infer_plutus_versions(&tx, &[]).unwrap();
+
+        assert_eq!(versions, BTreeSet::from([2]));
+    }
+
+    #[test]
+    fn assumes_default_for_unknown_languages() {
+        let hash = vec![0xab; 28];
+
+        let tx = ir::Tx {
+            fees: ir::Expression::None,
+            references: vec![],
+            inputs: vec![ir::Input {
+                name: "locked".to_string(),
+                query: None,
+                refs: Default::default(),
+                redeemer: Some(ir::Expression::Struct(ir::StructExpr::unit())),
+                policy: Some(ir::PolicyExpr {
+                    name: "Vault".to_string(),
+                    hash: ir::Expression::Hash(hash),
+                    script: None,
+                    language: None,
+                }),
+            }],
+            outputs: vec![],
+            validity: None,
+            mints: vec![],
+            adhoc: vec![],
+            collateral: vec![],
+            signers: None,
+            metadata: vec![],
+        };
+
+        let versions = infer_plutus_versions(&tx, &[]).unwrap();
+
+        assert_eq!(versions, BTreeSet::from([2]));
+    }
+
+    #[test]
+    fn dedups_scripts_by_hash() {
+        let script = hex::decode(ALWAYS_TRUE).unwrap();
+
+        let mut witnesses = ScriptWitnesses::default();
+        witnesses
+            .push(ir::ScriptLanguage::PlutusV3, &script)
+            .unwrap();
+        witnesses
+            .push(ir::ScriptLanguage::PlutusV3, &script)
+            .unwrap();
+
+        assert_eq!(witnesses.plutus_v3.len(), 1);
+        assert!(witnesses.plutus_v1.is_empty());
+    }
+
+    #[test]
+    fn infers_language_from_reference_utxo() {
+        let script = hex::decode(ALWAYS_TRUE).unwrap();
+        let hash = script_hash(ir::ScriptLanguage::PlutusV2, &script).unwrap();
+
+        let r#ref = tx3_lang::UtxoRef {
+            txid: vec![0xab; 32],
+            index: 0,
+        };
+
+        let policy = ir::PolicyExpr {
+            name: "MyPolicy".to_string(),
+            hash: ir::Expression::Hash(hash.to_vec()),
+            script: Some(ir::ScriptSource::UtxoRef {
+                r#ref: ir::Expression::UtxoRefs(vec![r#ref.clone()]),
+                source: None,
+            }),
+            language: None,
+        };
+
+        let script_ref =
+            primitives::ScriptRef::PlutusV2Script(primitives::PlutusScript(script.into()));
+
+        let utxo = tx3_lang::Utxo {
+            r#ref,
+            address: vec![],
+            datum: None,
+            datum_hash: None,
+            assets: vec![],
+            script: Some(ir::Expression::Bytes(minicbor::to_vec(script_ref).unwrap())),
+        };
+
+        assert_eq!(policy_language(&policy, &[]).unwrap(), None);
+
+        assert_eq!(
+            policy_language(&policy, &[utxo]).unwrap(),
+            Some(ir::ScriptLanguage::PlutusV2)
+        );
+    }
+}
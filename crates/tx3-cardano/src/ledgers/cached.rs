@@ -0,0 +1,204 @@
+//! A ledger decorator that memoizes the answers of another ledger.
+//!
+//! Resolving a tx evaluates it several times until the fees converge and each
+//! pass queries the ledger again for the same inputs. Wrapping the ledger in a
+//! [`CachedLedger`] for the duration of a resolution avoids the repeated
+//! round-trips. The tip is never cached since it's expected to move.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use tx3_lang::{ir::InputQuery, UtxoRef, UtxoSet};
+
+use crate::{resolve::Ledger, ChainPoint, Error, PParams, SlotConfig};
+
+#[derive(Default)]
+struct Cache {
+    pparams: Option<PParams>,
+    inputs: Vec<(InputQuery, UtxoSet)>,
+    collateral: Vec<(InputQuery, UtxoSet)>,
+    datums: HashMap<Vec<u8>, Option<Vec<u8>>>,
+    utxos: Vec<(Vec<UtxoRef>, UtxoSet)>,
+    slot_config: Option<SlotConfig>,
+}
+
+fn lookup<K: PartialEq, V: Clone>(entries: &[(K, V)], key: &K) -> Option<V> {
+    entries
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+}
+
+pub struct CachedLedger<L> {
+    inner: L,
+    cache: Mutex<Cache>,
+}
+
+impl<L> CachedLedger<L> {
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+
+    /// Whether the UTxOs of an input query are already cached, meaning that
+    /// resolving it won't reach the inner ledger.
+    pub fn has_input(&self, query: &InputQuery) -> bool {
+        lookup(&self.cache.lock().unwrap().inputs, query).is_some()
+    }
+
+    /// Whether the UTxOs of a collateral query are already cached.
+    pub fn has_collateral(&self, query: &InputQuery) -> bool {
+        lookup(&self.cache.lock().unwrap().collateral, query).is_some()
+    }
+}
+
+impl<L: Ledger + Sync> Ledger for CachedLedger<L> {
+    async fn get_pparams(&self) -> Result<PParams, Error> {
+        if let Some(pparams) = self.cache.lock().unwrap().pparams.clone() {
+            return Ok(pparams);
+        }
+
+        let pparams = self.inner.get_pparams().await?;
+        self.cache.lock().unwrap().pparams = Some(pparams.clone());
+
+        Ok(pparams)
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        if let Some(utxos) = lookup(&self.cache.lock().unwrap().inputs, query) {
+            return Ok(utxos);
+        }
+
+        let utxos = self.inner.resolve_input(query).await?;
+
+        self.cache
+            .lock()
+            .unwrap()
+            .inputs
+            .push((query.clone(), utxos.clone()));
+
+        Ok(utxos)
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        if let Some(utxos) = lookup(&self.cache.lock().unwrap().collateral, query) {
+            return Ok(utxos);
+        }
+
+        let utxos = self.inner.resolve_collateral(query).await?;
+
+        self.cache
+            .lock()
+            .unwrap()
+            .collateral
+            .push((query.clone(), utxos.clone()));
+
+        Ok(utxos)
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        if let Some(datum) = self.cache.lock().unwrap().datums.get(hash) {
+            return Ok(datum.clone());
+        }
+
+        let datum = self.inner.resolve_datum(hash).await?;
+
+        self.cache
+            .lock()
+            .unwrap()
+            .datums
+            .insert(hash.to_vec(), datum.clone());
+
+        Ok(datum)
+    }
+
+    async fn read_utxos(&self, refs: &[UtxoRef]) -> Result<UtxoSet, Error> {
+        let refs = refs.to_vec();
+
+        if let Some(utxos) = lookup(&self.cache.lock().unwrap().utxos, &refs) {
+            return Ok(utxos);
+        }
+
+        let utxos = self.inner.read_utxos(&refs).await?;
+
+        self.cache.lock().unwrap().utxos.push((refs, utxos.clone()));
+
+        Ok(utxos)
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        self.inner.get_tip().await
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        if let Some(config) = self.cache.lock().unwrap().slot_config.clone() {
+            return Ok(config);
+        }
+
+        let config = self.inner.get_slot_config().await?;
+        self.cache.lock().unwrap().slot_config = Some(config.clone());
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::ledgers::mock::MockLedger;
+
+    #[derive(Default)]
+    struct CountingLedger {
+        calls: AtomicUsize,
+    }
+
+    impl Ledger for CountingLedger {
+        async fn get_pparams(&self) -> Result<PParams, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            MockLedger.get_pparams().await
+        }
+
+        async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            MockLedger.resolve_input(query).await
+        }
+
+        async fn get_tip(&self) -> Result<ChainPoint, Error> {
+            MockLedger.get_tip().await
+        }
+
+        async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+            MockLedger.get_slot_config().await
+        }
+    }
+
+    #[tokio::test]
+    async fn memoizes_queries() {
+        let ledger = CachedLedger::new(CountingLedger::default());
+
+        let query = InputQuery {
+            address: Some(tx3_lang::ir::Expression::Address(vec![0x61; 29])),
+            ..Default::default()
+        };
+
+        let other = InputQuery {
+            address: Some(tx3_lang::ir::Expression::Address(vec![0x71; 29])),
+            ..Default::default()
+        };
+
+        ledger.get_pparams().await.unwrap();
+        ledger.get_pparams().await.unwrap();
+        ledger.resolve_input(&query).await.unwrap();
+        ledger.resolve_input(&query).await.unwrap();
+        ledger.resolve_input(&other).await.unwrap();
+
+        assert_eq!(ledger.into_inner().calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,467 @@
+//! An in-memory ledger that applies the transactions submitted to it.
+//!
+//! The emulator keeps a UTxO set and answers queries against it, which allows
+//! testing multi-step protocol flows without a node. Submitted transactions
+//! only go through a minimal check of their inputs; no other ledger rule is
+//! validated and scripts are not executed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use pallas::{
+    codec::minicbor,
+    crypto::hash::Hasher,
+    ledger::{
+        primitives::conway as primitives,
+        traverse::{MultiEraTx, OriginalHash as _},
+    },
+};
+use tx3_lang::{ir::InputQuery, Utxo, UtxoRef, UtxoSet};
+
+use super::selection::{is_pure_ada, min_amount_requirements, UtxoSelection};
+use crate::{
+    coercion, compile::plutus_data::TryIntoExpr as _, resolve::Ledger, submit::Submitter,
+    ChainPoint, Error, PParams, SlotConfig,
+};
+
+struct State {
+    utxos: Vec<Utxo>,
+    datums: HashMap<Vec<u8>, Vec<u8>>,
+    tip: ChainPoint,
+    genesis_count: u64,
+    submitted: HashSet<Vec<u8>>,
+}
+
+#[derive(Clone)]
+pub struct EmulatorLedger {
+    pparams: PParams,
+    slot_config: SlotConfig,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for EmulatorLedger {
+    fn default() -> Self {
+        Self::new(super::mock::pparams())
+    }
+}
+
+fn utxo_ref_to_string(r#ref: &UtxoRef) -> String {
+    format!("{}#{}", hex::encode(&r#ref.txid), r#ref.index)
+}
+
+fn output_into_utxo(
+    r#ref: UtxoRef,
+    output: &pallas::ledger::traverse::MultiEraOutput,
+) -> Result<Utxo, Error> {
+    let value = output.value();
+
+    let coin = tx3_lang::ir::AssetExpr {
+        policy: tx3_lang::ir::Expression::None,
+        asset_name: tx3_lang::ir::Expression::None,
+        amount: tx3_lang::ir::Expression::Number(value.coin() as i128),
+    };
+
+    let multiasset = value.assets().into_iter().flat_map(|policy| {
+        policy
+            .assets()
+            .into_iter()
+            .map(|asset| tx3_lang::ir::AssetExpr {
+                policy: tx3_lang::ir::Expression::Bytes(asset.policy().to_vec()),
+                asset_name: tx3_lang::ir::Expression::Bytes(asset.name().to_vec()),
+                amount: tx3_lang::ir::Expression::Number(asset.any_coin()),
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let (datum, datum_hash) = match output.datum() {
+        Some(primitives::DatumOption::Data(x)) => (Some(x.0.try_as_expr()?), None),
+        Some(primitives::DatumOption::Hash(x)) => (None, Some(x.to_vec())),
+        None => (None, None),
+    };
+
+    let script = output
+        .script_ref()
+        .map(minicbor::to_vec)
+        .transpose()
+        .map_err(|err| Error::MappingError(format!("can't encode script ref: {err}")))?
+        .map(tx3_lang::ir::Expression::Bytes);
+
+    Ok(Utxo {
+        r#ref,
+        address: output.address()?.to_vec(),
+        datum,
+        datum_hash,
+        assets: std::iter::once(coin).chain(multiasset).collect(),
+        script,
+    })
+}
+
+impl EmulatorLedger {
+    pub fn new(pparams: PParams) -> Self {
+        let slot_config = match pparams.network {
+            crate::Network::Mainnet => SlotConfig::mainnet(),
+            crate::Network::Testnet => SlotConfig::preview(),
+        };
+
+        let state = State {
+            utxos: vec![],
+            datums: HashMap::new(),
+            tip: ChainPoint {
+                slot: slot_config.zero_slot,
+                hash: vec![0; 32],
+            },
+            genesis_count: 0,
+            submitted: HashSet::new(),
+        };
+
+        Self {
+            pparams,
+            slot_config,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Creates a UTxO out of thin air holding the given lovelace at the
+    /// address, returning its reference.
+    pub fn fund(&self, address: &[u8], lovelace: u64) -> UtxoRef {
+        let mut state = self.state.lock().unwrap();
+
+        state.genesis_count += 1;
+
+        let r#ref = UtxoRef {
+            txid: Hasher::<256>::hash(&state.genesis_count.to_be_bytes()).to_vec(),
+            index: 0,
+        };
+
+        state.utxos.push(Utxo {
+            r#ref: r#ref.clone(),
+            address: address.to_vec(),
+            datum: None,
+            datum_hash: None,
+            assets: vec![tx3_lang::ir::AssetExpr {
+                policy: tx3_lang::ir::Expression::None,
+                asset_name: tx3_lang::ir::Expression::None,
+                amount: tx3_lang::ir::Expression::Number(lovelace as i128),
+            }],
+            script: None,
+        });
+
+        r#ref
+    }
+
+    /// Adds an existing UTxO to the set, as it would be found on chain.
+    pub fn add_utxo(&self, utxo: Utxo) {
+        self.state.lock().unwrap().utxos.push(utxo);
+    }
+
+    /// Makes the CBOR of a datum available to be looked up by its hash.
+    pub fn add_datum(&self, cbor: &[u8]) {
+        let hash = Hasher::<256>::hash(cbor).to_vec();
+        self.state
+            .lock()
+            .unwrap()
+            .datums
+            .insert(hash, cbor.to_vec());
+    }
+
+    /// Returns a snapshot of the current UTxO set.
+    pub fn utxos(&self) -> UtxoSet {
+        self.state.lock().unwrap().utxos.iter().cloned().collect()
+    }
+
+    /// Moves the tip of the chain forward by the given number of slots.
+    pub fn advance_slots(&self, slots: u64) {
+        self.state.lock().unwrap().tip.slot += slots;
+    }
+
+    /// Applies a compiled transaction to the UTxO set, returning its hash.
+    pub fn submit(&self, tx: &primitives::Tx) -> Result<Vec<u8>, Error> {
+        let payload = minicbor::to_vec(tx)
+            .map_err(|err| Error::MappingError(format!("can't encode tx: {err}")))?;
+
+        self.submit_payload(&payload)
+    }
+
+    /// Applies the CBOR of a transaction to the UTxO set, returning its hash.
+    pub fn submit_payload(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        let tx = MultiEraTx::decode(payload)
+            .map_err(|err| Error::MappingError(format!("invalid tx cbor: {err}")))?;
+
+        let hash = tx.hash().to_vec();
+
+        let consumed: Vec<_> = tx
+            .consumes()
+            .iter()
+            .map(|x| UtxoRef {
+                txid: x.hash().to_vec(),
+                index: x.index() as u32,
+            })
+            .collect();
+
+        let produced = tx
+            .produces()
+            .iter()
+            .map(|(index, output)| {
+                let r#ref = UtxoRef {
+                    txid: hash.clone(),
+                    index: *index as u32,
+                };
+
+                output_into_utxo(r#ref, output)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let datums = tx
+            .as_conway()
+            .and_then(|x| x.transaction_witness_set.plutus_data.as_ref())
+            .map(|x| {
+                x.iter()
+                    .map(|x| (x.original_hash().to_vec(), x.raw_cbor().to_vec()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(missing) = consumed
+            .iter()
+            .find(|r#ref| !state.utxos.iter().any(|x| &x.r#ref == *r#ref))
+        {
+            return Err(Error::InputNotFound(utxo_ref_to_string(missing)));
+        }
+
+        state.utxos.retain(|x| !consumed.contains(&x.r#ref));
+        state.utxos.extend(produced);
+        state.datums.extend(datums);
+        state.submitted.insert(hash.clone());
+
+        Ok(hash)
+    }
+
+    fn query_address(&self, query: &InputQuery) -> Result<Option<Vec<u8>>, Error> {
+        let address = query
+            .address
+            .as_ref()
+            .map(|x| coercion::expr_into_address(x, self.pparams.network))
+            .transpose()?
+            .map(|x| x.to_vec());
+
+        Ok(address)
+    }
+
+    fn candidates(&self, query: &InputQuery) -> Result<Vec<Utxo>, Error> {
+        let address = self.query_address(query)?;
+
+        let refs = query
+            .r#ref
+            .as_ref()
+            .map(coercion::expr_into_utxo_refs)
+            .transpose()?;
+
+        let state = self.state.lock().unwrap();
+
+        let out = state
+            .utxos
+            .iter()
+            .filter(|x| address.as_ref().is_none_or(|address| &x.address == address))
+            .filter(|x| refs.as_ref().is_none_or(|refs| refs.contains(&x.r#ref)))
+            .cloned()
+            .collect();
+
+        Ok(out)
+    }
+}
+
+impl Ledger for EmulatorLedger {
+    async fn get_pparams(&self) -> Result<PParams, Error> {
+        Ok(self.pparams.clone())
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        let candidates = self.candidates(query)?;
+
+        // utxos requested by reference are returned as they are
+        if query.r#ref.is_some() {
+            return Ok(candidates.into_iter().collect());
+        }
+
+        let mut selection = UtxoSelection::new(min_amount_requirements(query)?);
+
+        for utxo in candidates {
+            selection.offer(utxo)?;
+        }
+
+        if !selection.is_satisfied() {
+            return Ok(UtxoSet::new());
+        }
+
+        Ok(selection.selected.into_iter().collect())
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        let utxos = self
+            .candidates(query)?
+            .into_iter()
+            .filter(is_pure_ada)
+            .collect();
+
+        Ok(utxos)
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.state.lock().unwrap().datums.get(hash).cloned())
+    }
+
+    async fn read_utxos(&self, refs: &[UtxoRef]) -> Result<UtxoSet, Error> {
+        let state = self.state.lock().unwrap();
+
+        let utxos = state
+            .utxos
+            .iter()
+            .filter(|x| refs.contains(&x.r#ref))
+            .cloned()
+            .collect();
+
+        Ok(utxos)
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        Ok(self.state.lock().unwrap().tip.clone())
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        Ok(self.slot_config.clone())
+    }
+}
+
+impl Submitter for EmulatorLedger {
+    async fn submit_tx(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        self.submit_payload(payload)
+    }
+
+    async fn wait_for_confirmation(&self, hash: &[u8]) -> Result<(), Error> {
+        // txs are applied as soon as they're submitted
+        match self.state.lock().unwrap().submitted.contains(hash) {
+            true => Ok(()),
+            false => Err(Error::TxNotFound(hex::encode(hash))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tx3_lang::{ArgValue, Protocol};
+
+    use super::*;
+    use crate::{resolve_tx, resolve_tx_with_options, ResolveOptions};
+
+    const ADDRESS: &str = "addr_test1qz0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6qllhhm4";
+
+    fn load_protocol(example_name: &str) -> Protocol {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let code = format!("{manifest_dir}/../../examples/{example_name}.tx3");
+        Protocol::from_file(&code).load().unwrap()
+    }
+
+    fn address_bytes() -> Vec<u8> {
+        pallas::ledger::addresses::Address::from_bech32(ADDRESS)
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn vesting_lock_and_unlock() {
+        let ledger = EmulatorLedger::default();
+        let funds = ledger.fund(&address_bytes(), 500_000_000);
+
+        let protocol = load_protocol("vesting");
+
+        let lock = protocol
+            .new_tx("lock")
+            .unwrap()
+            .with_arg("Owner", ArgValue::Address(address_bytes()))
+            .with_arg("Beneficiary", ArgValue::Address(address_bytes()))
+            .with_arg("quantity", ArgValue::Int(100_000_000))
+            .with_arg("until", ArgValue::Int(1713288000))
+            .apply()
+            .unwrap();
+
+        let options = ResolveOptions {
+            validate: true,
+            ..Default::default()
+        };
+
+        let lock = resolve_tx_with_options(lock, ledger.clone(), &options)
+            .await
+            .unwrap();
+        let lock_hash = ledger.submit_payload(&lock.payload).unwrap();
+
+        let utxos = ledger.utxos();
+        assert_eq!(utxos.len(), 2);
+        assert!(utxos.iter().all(|x| x.r#ref != funds));
+
+        let locked = UtxoRef {
+            txid: lock_hash,
+            index: 0,
+        };
+
+        let locked_utxo = utxos.iter().find(|x| x.r#ref == locked).unwrap();
+        assert!(locked_utxo.datum.is_some());
+
+        let unlock = protocol
+            .new_tx("unlock")
+            .unwrap()
+            .with_arg("Beneficiary", ArgValue::Address(address_bytes()))
+            .with_arg("locked_utxo", ArgValue::UtxoRef(locked.clone()))
+            .apply()
+            .unwrap();
+
+        // the protocol only knows the hash of its validator, so the units of
+        // the unlock can't be evaluated
+        let result = resolve_tx(unlock, ledger.clone(), 3).await;
+        assert!(result.is_err());
+
+        // spending the same utxo twice is rejected
+        let result = ledger.submit_payload(&lock.payload);
+        assert!(matches!(result, Err(Error::InputNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn answers_queries_by_amount() {
+        let ledger = EmulatorLedger::default();
+        ledger.fund(&address_bytes(), 3_000_000);
+        ledger.fund(&address_bytes(), 4_000_000);
+        ledger.fund(&[0x61; 29], 10_000_000);
+
+        let query = InputQuery {
+            address: Some(tx3_lang::ir::Expression::Address(address_bytes())),
+            min_amount: Some(tx3_lang::ir::Expression::Assets(vec![
+                tx3_lang::ir::AssetExpr {
+                    policy: tx3_lang::ir::Expression::None,
+                    asset_name: tx3_lang::ir::Expression::None,
+                    amount: tx3_lang::ir::Expression::Number(5_000_000),
+                },
+            ])),
+            r#ref: None,
+        };
+
+        let utxos = ledger.resolve_input(&query).await.unwrap();
+        assert_eq!(utxos.len(), 2);
+
+        let query = InputQuery {
+            min_amount: Some(tx3_lang::ir::Expression::Assets(vec![
+                tx3_lang::ir::AssetExpr {
+                    policy: tx3_lang::ir::Expression::None,
+                    asset_name: tx3_lang::ir::Expression::None,
+                    amount: tx3_lang::ir::Expression::Number(8_000_000),
+                },
+            ])),
+            ..query
+        };
+
+        let utxos = ledger.resolve_input(&query).await.unwrap();
+        assert!(utxos.is_empty());
+    }
+}
@@ -0,0 +1,675 @@
+//! A ledger loaded from a JSON or YAML fixture file.
+//!
+//! Fixtures describe the protocol parameters and the UTxOs available on chain
+//! following the shape of [`tx3_lang::Utxo`], with binary values encoded as
+//! hex. Queries are answered by an [`EmulatorLedger`] seeded with the fixture
+//! contents. The pparams are required, every other section is optional.
+//!
+//! ```json
+//! {
+//!   "pparams": { "network": "testnet", "min_fee_coefficient": 44, ... },
+//!   "utxos": [
+//!     {
+//!       "ref": "<txid>#0",
+//!       "address": "addr_test1...",
+//!       "assets": [{ "amount": 5000000 }, { "policy": "<hex>", "name": "<hex>", "amount": 1 }],
+//!       "datum": "<plutus data cbor>",
+//!       "script": "<script ref cbor>"
+//!     }
+//!   ],
+//!   "datums": ["<plutus data cbor>"],
+//!   "inputs": [{ "query": { ... }, "utxos": ["<txid>#0"] }],
+//!   "collateral": [{ "query": { ... }, "utxos": ["<txid>#0"] }],
+//!   "tip": { "slot": 1000, "hash": "<block hash>" },
+//!   "slot_config": { "zero_time": 1666656000000, "zero_slot": 0, "slot_length": 1000 }
+//! }
+//! ```
+//!
+//! The YAML encoding has the same shape:
+//!
+//! ```yaml
+//! pparams:
+//!   network: testnet
+//!   min_fee_coefficient: 44
+//!   # ...
+//! utxos:
+//!   - ref: "<txid>#0"
+//!     address: addr_test1...
+//!     assets:
+//!       - amount: 5000000
+//! ```
+//!
+//! The `inputs` and `collateral` sections hold answers recorded for specific
+//! queries, which are replayed as they are instead of selecting UTxOs again.
+//! Recorded UTxOs that were spent since are left out of the answers.
+
+use std::collections::HashMap;
+
+use pallas::{codec::minicbor, ledger::primitives::PlutusData};
+use serde::{Deserialize, Serialize};
+use tx3_lang::{ir, ir::InputQuery, Utxo, UtxoRef, UtxoSet};
+
+use super::emulator::EmulatorLedger;
+use crate::{
+    coercion,
+    compile::plutus_data::{TryIntoData as _, TryIntoExpr as _},
+    resolve::Ledger,
+    ChainPoint, CostModel, Error, ExUnitPrices, ExUnits, PParams, PlutusVersion, Ratio, SlotConfig,
+};
+
+#[derive(Serialize, Deserialize)]
+struct FixturePParams {
+    network: String,
+    min_fee_coefficient: u64,
+    min_fee_constant: u64,
+    coins_per_utxo_byte: u64,
+    #[serde(default)]
+    cost_models: HashMap<PlutusVersion, CostModel>,
+    collateral_percentage: u64,
+    max_collateral_inputs: u64,
+    max_tx_size: u64,
+    max_value_size: u64,
+    execution_prices: ExUnitPrices,
+    max_tx_ex_units: ExUnits,
+    key_deposit: u64,
+    pool_deposit: u64,
+    min_fee_ref_script_cost_per_byte: Ratio,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureAsset {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    policy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    amount: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureUtxo {
+    r#ref: String,
+    address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    datum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    datum_hash: Option<String>,
+    #[serde(default)]
+    assets: Vec<FixtureAsset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    script: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureAnswer {
+    query: InputQuery,
+    utxos: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureTip {
+    slot: u64,
+    hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pparams: Option<FixturePParams>,
+    #[serde(default)]
+    utxos: Vec<FixtureUtxo>,
+    #[serde(default)]
+    datums: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inputs: Vec<FixtureAnswer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    collateral: Vec<FixtureAnswer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tip: Option<FixtureTip>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slot_config: Option<SlotConfig>,
+}
+
+/// Answers of a ledger to specific queries, replayed as they are.
+#[derive(Clone, Default)]
+pub(crate) struct Answers {
+    pub inputs: Vec<(InputQuery, Vec<UtxoRef>)>,
+    pub collateral: Vec<(InputQuery, Vec<UtxoRef>)>,
+    pub tip: Option<ChainPoint>,
+    pub slot_config: Option<SlotConfig>,
+}
+
+impl Answers {
+    /// Records the answer to a query, replacing any previous one.
+    pub fn record(
+        answers: &mut Vec<(InputQuery, Vec<UtxoRef>)>,
+        query: &InputQuery,
+        utxos: &UtxoSet,
+    ) {
+        let mut refs: Vec<_> = utxos.iter().map(|x| x.r#ref.clone()).collect();
+        refs.sort_by(|a, b| (&a.txid, a.index).cmp(&(&b.txid, b.index)));
+
+        answers.retain(|(x, _)| x != query);
+        answers.push((query.clone(), refs));
+    }
+
+    fn lookup<'a>(
+        answers: &'a [(InputQuery, Vec<UtxoRef>)],
+        query: &InputQuery,
+    ) -> Option<&'a [UtxoRef]> {
+        answers
+            .iter()
+            .find(|(x, _)| x == query)
+            .map(|(_, refs)| refs.as_slice())
+    }
+}
+
+impl From<&(InputQuery, Vec<UtxoRef>)> for FixtureAnswer {
+    fn from((query, refs): &(InputQuery, Vec<UtxoRef>)) -> Self {
+        FixtureAnswer {
+            query: query.clone(),
+            utxos: refs
+                .iter()
+                .map(|x| format!("{}#{}", hex::encode(&x.txid), x.index))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<FixtureAnswer> for (InputQuery, Vec<UtxoRef>) {
+    type Error = Error;
+
+    fn try_from(value: FixtureAnswer) -> Result<Self, Self::Error> {
+        let refs = value
+            .utxos
+            .iter()
+            .map(|x| parse_utxo_ref(x))
+            .collect::<Result<_, _>>()?;
+
+        Ok((value.query, refs))
+    }
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(value).map_err(|err| Error::InvalidFixture(format!("{field} '{value}': {err}")))
+}
+
+fn parse_utxo_ref(value: &str) -> Result<UtxoRef, Error> {
+    let invalid = || Error::InvalidFixture(format!("utxo ref '{value}'"));
+
+    let (txid, index) = value.split_once('#').ok_or_else(invalid)?;
+
+    Ok(UtxoRef {
+        txid: decode_hex("txid", txid)?,
+        index: index.parse().map_err(|_| invalid())?,
+    })
+}
+
+impl TryFrom<FixturePParams> for PParams {
+    type Error = Error;
+
+    fn try_from(value: FixturePParams) -> Result<Self, Self::Error> {
+        let network = match value.network.as_str() {
+            "mainnet" => crate::Network::Mainnet,
+            "testnet" => crate::Network::Testnet,
+            x => return Err(Error::InvalidFixture(format!("network '{x}'"))),
+        };
+
+        Ok(PParams {
+            network,
+            min_fee_coefficient: value.min_fee_coefficient,
+            min_fee_constant: value.min_fee_constant,
+            coins_per_utxo_byte: value.coins_per_utxo_byte,
+            cost_models: value.cost_models,
+            collateral_percentage: value.collateral_percentage,
+            max_collateral_inputs: value.max_collateral_inputs,
+            max_tx_size: value.max_tx_size,
+            max_value_size: value.max_value_size,
+            execution_prices: value.execution_prices,
+            max_tx_ex_units: value.max_tx_ex_units,
+            key_deposit: value.key_deposit,
+            pool_deposit: value.pool_deposit,
+            min_fee_ref_script_cost_per_byte: value.min_fee_ref_script_cost_per_byte,
+        })
+    }
+}
+
+impl TryFrom<FixtureAsset> for ir::AssetExpr {
+    type Error = Error;
+
+    fn try_from(value: FixtureAsset) -> Result<Self, Self::Error> {
+        let bytes = |field, value: Option<String>| match value {
+            Some(x) => decode_hex(field, &x).map(ir::Expression::Bytes),
+            None => Ok(ir::Expression::None),
+        };
+
+        Ok(ir::AssetExpr {
+            policy: bytes("policy", value.policy)?,
+            asset_name: bytes("asset name", value.name)?,
+            amount: ir::Expression::Number(value.amount as i128),
+        })
+    }
+}
+
+impl TryFrom<FixtureUtxo> for Utxo {
+    type Error = Error;
+
+    fn try_from(value: FixtureUtxo) -> Result<Self, Self::Error> {
+        let datum = value
+            .datum
+            .map(|x| {
+                let cbor = decode_hex("datum", &x)?;
+
+                let data: PlutusData = minicbor::decode(&cbor)
+                    .map_err(|err| Error::InvalidFixture(format!("datum '{x}': {err}")))?;
+
+                data.try_as_expr()
+            })
+            .transpose()?;
+
+        let datum_hash = value
+            .datum_hash
+            .map(|x| decode_hex("datum hash", &x))
+            .transpose()?;
+
+        let script = value
+            .script
+            .map(|x| decode_hex("script", &x).map(ir::Expression::Bytes))
+            .transpose()?;
+
+        let assets = value
+            .assets
+            .into_iter()
+            .map(ir::AssetExpr::try_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Utxo {
+            r#ref: parse_utxo_ref(&value.r#ref)?,
+            address: coercion::string_into_address(&value.address)?.to_vec(),
+            datum,
+            datum_hash,
+            assets,
+            script,
+        })
+    }
+}
+
+impl From<&PParams> for FixturePParams {
+    fn from(value: &PParams) -> Self {
+        let network = match value.network {
+            crate::Network::Mainnet => "mainnet",
+            crate::Network::Testnet => "testnet",
+        };
+
+        FixturePParams {
+            network: network.to_string(),
+            min_fee_coefficient: value.min_fee_coefficient,
+            min_fee_constant: value.min_fee_constant,
+            coins_per_utxo_byte: value.coins_per_utxo_byte,
+            cost_models: value.cost_models.clone(),
+            collateral_percentage: value.collateral_percentage,
+            max_collateral_inputs: value.max_collateral_inputs,
+            max_tx_size: value.max_tx_size,
+            max_value_size: value.max_value_size,
+            execution_prices: value.execution_prices,
+            max_tx_ex_units: value.max_tx_ex_units,
+            key_deposit: value.key_deposit,
+            pool_deposit: value.pool_deposit,
+            min_fee_ref_script_cost_per_byte: value.min_fee_ref_script_cost_per_byte,
+        }
+    }
+}
+
+impl TryFrom<&ir::AssetExpr> for FixtureAsset {
+    type Error = Error;
+
+    fn try_from(value: &ir::AssetExpr) -> Result<Self, Self::Error> {
+        let bytes = |expr: &ir::Expression| match expr {
+            ir::Expression::None => Ok(None),
+            x => coercion::expr_into_bytes(x).map(|x| Some(hex::encode(x.as_slice()))),
+        };
+
+        let amount = coercion::expr_into_number(&value.amount)?;
+
+        Ok(FixtureAsset {
+            policy: bytes(&value.policy)?,
+            name: bytes(&value.asset_name)?,
+            amount: u64::try_from(amount)
+                .map_err(|_| Error::InvalidAssetExpression(format!("{:?}", value.amount)))?,
+        })
+    }
+}
+
+impl TryFrom<&Utxo> for FixtureUtxo {
+    type Error = Error;
+
+    fn try_from(value: &Utxo) -> Result<Self, Self::Error> {
+        let datum = value
+            .datum
+            .as_ref()
+            .map(|x| {
+                let cbor = minicbor::to_vec(x.try_as_data()?)
+                    .map_err(|err| Error::MappingError(format!("can't encode datum: {err}")))?;
+
+                Ok::<_, Error>(hex::encode(cbor))
+            })
+            .transpose()?;
+
+        let script = value
+            .script
+            .as_ref()
+            .map(|x| coercion::expr_into_bytes(x).map(|x| hex::encode(x.as_slice())))
+            .transpose()?;
+
+        let assets = value
+            .assets
+            .iter()
+            .map(FixtureAsset::try_from)
+            .collect::<Result<_, _>>()?;
+
+        Ok(FixtureUtxo {
+            r#ref: format!("{}#{}", hex::encode(&value.r#ref.txid), value.r#ref.index),
+            address: hex::encode(&value.address),
+            datum,
+            datum_hash: value.datum_hash.as_ref().map(hex::encode),
+            assets,
+            script,
+        })
+    }
+}
+
+/// Encodes ledger state and the answers recorded from it into a JSON
+/// fixture that [`FileLedger`] can load.
+pub(crate) fn fixture_json(
+    pparams: Option<&PParams>,
+    utxos: &[Utxo],
+    datums: &[Vec<u8>],
+    answers: &Answers,
+) -> Result<String, Error> {
+    let fixture = Fixture {
+        pparams: pparams.map(FixturePParams::from),
+        utxos: utxos
+            .iter()
+            .map(FixtureUtxo::try_from)
+            .collect::<Result<_, _>>()?,
+        datums: datums.iter().map(hex::encode).collect(),
+        inputs: answers.inputs.iter().map(FixtureAnswer::from).collect(),
+        collateral: answers.collateral.iter().map(FixtureAnswer::from).collect(),
+        tip: answers.tip.as_ref().map(|x| FixtureTip {
+            slot: x.slot,
+            hash: hex::encode(&x.hash),
+        }),
+        slot_config: answers.slot_config.clone(),
+    };
+
+    serde_json::to_string_pretty(&fixture).map_err(|err| Error::InvalidFixture(err.to_string()))
+}
+
+#[derive(Clone)]
+pub struct FileLedger {
+    inner: EmulatorLedger,
+    answers: Answers,
+}
+
+impl FileLedger {
+    /// Loads a ledger from the fixture at the given path, which is read as
+    /// YAML when its extension is `yaml` or `yml` and as JSON otherwise.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| Error::InvalidFixture(format!("{}: {err}", path.display())))?;
+
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("yaml" | "yml") => Self::from_yaml(&contents),
+            _ => Self::from_json(&contents),
+        }
+    }
+
+    /// Loads a ledger from the contents of a JSON fixture.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let fixture: Fixture =
+            serde_json::from_str(json).map_err(|err| Error::InvalidFixture(err.to_string()))?;
+
+        Self::from_fixture(fixture)
+    }
+
+    /// Loads a ledger from the contents of a YAML fixture.
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        let fixture: Fixture =
+            serde_yaml::from_str(yaml).map_err(|err| Error::InvalidFixture(err.to_string()))?;
+
+        Self::from_fixture(fixture)
+    }
+
+    fn from_fixture(fixture: Fixture) -> Result<Self, Error> {
+        let pparams = fixture
+            .pparams
+            .ok_or_else(|| Error::InvalidFixture("missing pparams".to_string()))?;
+
+        let pparams = PParams::try_from(pparams)?;
+
+        let inner = EmulatorLedger::new(pparams);
+
+        for utxo in fixture.utxos {
+            inner.add_utxo(Utxo::try_from(utxo)?);
+        }
+
+        for datum in fixture.datums {
+            inner.add_datum(&decode_hex("datum", &datum)?);
+        }
+
+        let answers = Answers {
+            inputs: fixture
+                .inputs
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+            collateral: fixture
+                .collateral
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+            tip: fixture
+                .tip
+                .map(|x| {
+                    Ok::<_, Error>(ChainPoint {
+                        slot: x.slot,
+                        hash: decode_hex("tip hash", &x.hash)?,
+                    })
+                })
+                .transpose()?,
+            slot_config: fixture.slot_config,
+        };
+
+        Ok(Self { inner, answers })
+    }
+
+    /// Gives access to the underlying emulator, for example to submit txs
+    /// on top of the fixture state.
+    pub fn emulator(&self) -> &EmulatorLedger {
+        &self.inner
+    }
+}
+
+impl Ledger for FileLedger {
+    async fn get_pparams(&self) -> Result<PParams, Error> {
+        self.inner.get_pparams().await
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        match Answers::lookup(&self.answers.inputs, query) {
+            Some(refs) => self.inner.read_utxos(refs).await,
+            None => self.inner.resolve_input(query).await,
+        }
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        match Answers::lookup(&self.answers.collateral, query) {
+            Some(refs) => self.inner.read_utxos(refs).await,
+            None => self.inner.resolve_collateral(query).await,
+        }
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.inner.resolve_datum(hash).await
+    }
+
+    async fn read_utxos(&self, refs: &[UtxoRef]) -> Result<UtxoSet, Error> {
+        self.inner.read_utxos(refs).await
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        match &self.answers.tip {
+            Some(tip) => Ok(tip.clone()),
+            None => self.inner.get_tip().await,
+        }
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        match &self.answers.slot_config {
+            Some(config) => Ok(config.clone()),
+            None => self.inner.get_slot_config().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Encodes a JSON fixture with the given sections plus the pparams, which
+    /// every fixture needs.
+    fn fixture(mut sections: serde_json::Value) -> String {
+        let pparams = FixturePParams::from(&crate::ledgers::mock::pparams());
+        sections["pparams"] = serde_json::to_value(pparams).unwrap();
+        sections.to_string()
+    }
+
+    #[test]
+    fn rejects_invalid_refs() {
+        let json = fixture(json!({ "utxos": [{ "ref": "abcd", "address": "", "assets": [] }] }));
+
+        let result = FileLedger::from_json(&json);
+
+        assert!(matches!(result, Err(Error::InvalidFixture(x)) if x.contains("abcd")));
+    }
+
+    #[test]
+    fn requires_pparams() {
+        let result = FileLedger::from_json(r#"{ "utxos": [] }"#);
+
+        assert!(matches!(result, Err(Error::InvalidFixture(x)) if x == "missing pparams"));
+    }
+
+    #[tokio::test]
+    async fn loads_utxos_with_assets() {
+        let json = fixture(json!({
+            "utxos": [{
+                "ref": "0000000000000000000000000000000000000000000000000000000000000000#3",
+                "address": "706b9c456aa650cb808a9ab54326e039d5235ed69f069c9664a8fe5b69",
+                "assets": [
+                    { "amount": 2000000 },
+                    { "policy": "abababababababababababababababababababababababababababab", "name": "4d59544f4b454e", "amount": 1 }
+                ],
+                "datum": "d8799f182aff"
+            }]
+        }));
+
+        let ledger = FileLedger::from_json(&json).unwrap();
+
+        let utxos = ledger
+            .read_utxos(&[UtxoRef {
+                txid: vec![0; 32],
+                index: 3,
+            }])
+            .await
+            .unwrap();
+
+        let utxo = utxos.iter().next().unwrap();
+
+        assert_eq!(utxo.assets.len(), 2);
+        assert_eq!(
+            utxo.datum,
+            Some(ir::Expression::Struct(ir::StructExpr {
+                constructor: 0,
+                fields: vec![ir::Expression::Number(42)],
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_answers() {
+        let query = InputQuery {
+            address: Some(ir::Expression::Address(vec![0x61; 29])),
+            ..Default::default()
+        };
+
+        let utxo = |index: u32, amount: u64| {
+            json!({
+                "ref": format!("{}#{index}", "00".repeat(32)),
+                "address": "61".repeat(29),
+                "assets": [{ "amount": amount }],
+            })
+        };
+
+        // without the recorded answer, the emulator could pick either utxo
+        let json = fixture(json!({
+            "utxos": [utxo(0, 9_000_000), utxo(1, 2_000_000)],
+            "inputs": [{ "query": query, "utxos": [format!("{}#1", "00".repeat(32))] }],
+            "tip": { "slot": 1234, "hash": "abcd" },
+            "slot_config": { "zero_time": 0, "zero_slot": 0, "slot_length": 1000 },
+        }));
+
+        let ledger = FileLedger::from_json(&json).unwrap();
+
+        let utxos = ledger.resolve_input(&query).await.unwrap();
+        let indexes: Vec<_> = utxos.iter().map(|x| x.r#ref.index).collect();
+        assert_eq!(indexes, vec![1]);
+
+        assert_eq!(ledger.get_tip().await.unwrap().slot, 1234);
+        assert_eq!(ledger.get_slot_config().await.unwrap().zero_time, 0);
+    }
+
+    #[tokio::test]
+    async fn loads_yaml_fixtures() {
+        let yaml = r#"
+pparams:
+  network: testnet
+  min_fee_coefficient: 44
+  min_fee_constant: 155381
+  coins_per_utxo_byte: 4310
+  collateral_percentage: 150
+  max_collateral_inputs: 3
+  max_tx_size: 16384
+  max_value_size: 5000
+  execution_prices:
+    mem: { numerator: 577, denominator: 10000 }
+    steps: { numerator: 721, denominator: 10000000 }
+  max_tx_ex_units: { mem: 14000000, steps: 10000000000 }
+  key_deposit: 2000000
+  pool_deposit: 500000000
+  min_fee_ref_script_cost_per_byte: { numerator: 15, denominator: 1 }
+utxos:
+  - ref: "0000000000000000000000000000000000000000000000000000000000000000#0"
+    address: "6161616161616161616161616161616161616161616161616161616161"
+    assets:
+      - amount: 5000000
+"#;
+
+        let ledger = FileLedger::from_yaml(yaml).unwrap();
+
+        assert_eq!(ledger.get_pparams().await.unwrap().min_fee_constant, 155381);
+
+        let utxos = ledger
+            .read_utxos(&[UtxoRef {
+                txid: vec![0; 32],
+                index: 0,
+            }])
+            .await
+            .unwrap();
+
+        assert_eq!(utxos.len(), 1);
+    }
+}
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use tx3_lang::{ir::InputQuery, UtxoSet};
 
-use crate::{resolve::Ledger, Error, PParams};
+use crate::{
+    resolve::Ledger, ChainPoint, Error, ExUnitPrices, ExUnits, PParams, Ratio, SlotConfig,
+};
 
 const COST_MODEL_PLUTUS_V1: [i64; 166] = [
     100788, 420, 1, 1, 1000, 173, 0, 1, 1000, 59957, 4, 1, 11183, 32, 201305, 8356, 4, 16000, 100,
@@ -30,20 +32,72 @@ static COST_MODEL_PLUTUS_V2: [i64; 175] = [
     32, 24623, 32, 43053543, 10, 53384111, 14333, 10, 43574283, 26308, 10,
 ];
 
+static COST_MODEL_PLUTUS_V3: [i64; 297] = [
+    100788, 420, 1, 1, 1000, 173, 0, 1, 1000, 59957, 4, 1, 11183, 32, 201305, 8356, 4, 16000, 100,
+    16000, 100, 16000, 100, 16000, 100, 16000, 100, 16000, 100, 100, 100, 16000, 100, 94375, 32,
+    132994, 32, 61462, 4, 72010, 178, 0, 1, 22151, 32, 91189, 769, 4, 2, 85848, 123203, 7305, -900,
+    1716, 549, 57, 85848, 0, 1, 1, 1000, 42921, 4, 2, 24548, 29498, 38, 1, 898148, 27279, 1, 51775,
+    558, 1, 39184, 1000, 60594, 1, 141895, 32, 83150, 32, 15299, 32, 76049, 1, 13169, 4, 22100, 10,
+    28999, 74, 1, 28999, 74, 1, 43285, 552, 1, 44749, 541, 1, 33852, 32, 68246, 32, 72362, 32,
+    7243, 32, 7391, 32, 11546, 32, 85848, 123203, 7305, -900, 1716, 549, 57, 85848, 0, 1, 90434,
+    519, 0, 1, 74433, 32, 85848, 123203, 7305, -900, 1716, 549, 57, 85848, 0, 1, 1, 85848, 123203,
+    7305, -900, 1716, 549, 57, 85848, 0, 1, 955506, 213312, 0, 2, 270652, 22588, 4, 1457325, 64566,
+    4, 20467, 1, 4, 0, 141992, 32, 100788, 420, 1, 1, 81663, 32, 59498, 32, 20142, 32, 24588, 32,
+    20744, 32, 25933, 32, 24623, 32, 43053543, 10, 53384111, 14333, 10, 43574283, 26308, 10, 16000,
+    100, 16000, 100, 962335, 18, 2780678, 6, 442008, 1, 52538055, 3756, 18, 267929, 18, 76433006,
+    8868, 18, 52948122, 18, 1995836, 36, 3227919, 12, 901022, 1, 166917843, 4307, 36, 284546, 36,
+    158221314, 26549, 36, 74698472, 36, 333849714, 1, 254006273, 72, 2174038, 72, 2261318, 64571,
+    4, 207616, 8310, 4, 1293828, 28716, 63, 0, 1, 1006041, 43623, 251, 0, 1, 100181, 726, 719, 0,
+    1, 100181, 726, 719, 0, 1, 100181, 726, 719, 0, 1, 107878, 680, 0, 1, 95336, 1, 281145, 18848,
+    0, 1, 180194, 159, 1, 1, 158519, 8942, 0, 1, 159378, 8813, 0, 1, 107490, 3298, 1, 106057, 655,
+    1, 1964219, 24520, 3,
+];
+
+/// Protocol parameters shared by the ledgers meant for testing.
+pub fn pparams() -> PParams {
+    PParams {
+        network: crate::Network::Testnet,
+        min_fee_coefficient: 1,
+        min_fee_constant: 2,
+        coins_per_utxo_byte: 1,
+        cost_models: HashMap::from([
+            (0, COST_MODEL_PLUTUS_V1.to_vec()),
+            (1, COST_MODEL_PLUTUS_V2.to_vec()),
+            (2, COST_MODEL_PLUTUS_V3.to_vec()),
+        ]),
+        collateral_percentage: 150,
+        max_collateral_inputs: 3,
+        max_tx_size: 16384,
+        max_value_size: 5000,
+        execution_prices: ExUnitPrices {
+            mem: Ratio {
+                numerator: 577,
+                denominator: 10000,
+            },
+            steps: Ratio {
+                numerator: 721,
+                denominator: 10000000,
+            },
+        },
+        max_tx_ex_units: ExUnits {
+            mem: 14000000,
+            steps: 10000000000,
+        },
+        key_deposit: 2000000,
+        pool_deposit: 500000000,
+        min_fee_ref_script_cost_per_byte: Ratio {
+            numerator: 15,
+            denominator: 1,
+        },
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct MockLedger;
 
 impl Ledger for MockLedger {
     async fn get_pparams(&self) -> Result<PParams, Error> {
-        Ok(PParams {
-            network: crate::Network::Testnet,
-            min_fee_coefficient: 1,
-            min_fee_constant: 2,
-            coins_per_utxo_byte: 1,
-            cost_models: HashMap::from([
-                (0, COST_MODEL_PLUTUS_V1.to_vec()),
-                (1, COST_MODEL_PLUTUS_V2.to_vec()),
-            ]),
-        })
+        Ok(pparams())
     }
 
     async fn resolve_input(&self, _input: &InputQuery) -> Result<UtxoSet, Error> {
@@ -57,6 +111,7 @@ impl Ledger for MockLedger {
         },
         address: pallas::ledger::addresses::Address::from_bech32("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2").unwrap().to_vec(),
         datum: None,
+        datum_hash: None,
         assets: vec![tx3_lang::ir::AssetExpr {
             policy: tx3_lang::ir::Expression::None,
             asset_name: tx3_lang::ir::Expression::None,
@@ -67,4 +122,15 @@ impl Ledger for MockLedger {
 
         Ok(UtxoSet::from([utxo]))
     }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        Ok(ChainPoint {
+            slot: 100_000_000,
+            hash: vec![0; 32],
+        })
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        Ok(SlotConfig::preview())
+    }
 }
@@ -1,2 +1,8 @@
+pub mod cached;
+pub mod emulator;
+pub mod file;
 pub mod mock;
+pub mod recording;
 pub mod u5c;
+
+pub(crate) mod selection;
@@ -0,0 +1,180 @@
+//! A ledger decorator that records the answers of another ledger.
+//!
+//! Every UTxO, datum and pparams returned while resolving a tx is captured so
+//! it can be saved as a fixture and replayed later through a
+//! [`FileLedger`](super::file::FileLedger), for example to reproduce a bug
+//! report without access to the original chain. The answers to input queries,
+//! the tip and the slot config are recorded as well, so the replay answers
+//! each query the same way the original ledger did.
+
+use std::sync::Mutex;
+
+use tx3_lang::{ir::InputQuery, Utxo, UtxoRef, UtxoSet};
+
+use super::file::Answers;
+use crate::{resolve::Ledger, ChainPoint, Error, PParams, SlotConfig};
+
+#[derive(Default)]
+struct Recording {
+    pparams: Option<PParams>,
+    utxos: Vec<Utxo>,
+    datums: Vec<Vec<u8>>,
+    answers: Answers,
+}
+
+impl Recording {
+    fn record_utxos(&mut self, utxos: &UtxoSet) {
+        for utxo in utxos {
+            if !self.utxos.iter().any(|x| x.r#ref == utxo.r#ref) {
+                self.utxos.push(utxo.clone());
+            }
+        }
+    }
+}
+
+pub struct RecordingLedger<L> {
+    inner: L,
+    recording: Mutex<Recording>,
+}
+
+impl<L> RecordingLedger<L> {
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            recording: Mutex::new(Recording::default()),
+        }
+    }
+
+    /// Encodes everything recorded so far as a JSON fixture.
+    pub fn to_fixture_json(&self) -> Result<String, Error> {
+        let recording = self.recording.lock().unwrap();
+
+        super::file::fixture_json(
+            recording.pparams.as_ref(),
+            &recording.utxos,
+            &recording.datums,
+            &recording.answers,
+        )
+    }
+
+    /// Saves everything recorded so far as a JSON fixture at the given path.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let json = self.to_fixture_json()?;
+
+        std::fs::write(path, json)
+            .map_err(|err| Error::InvalidFixture(format!("{}: {err}", path.display())))
+    }
+}
+
+impl<L: Ledger + Sync> Ledger for RecordingLedger<L> {
+    async fn get_pparams(&self) -> Result<PParams, Error> {
+        let pparams = self.inner.get_pparams().await?;
+        self.recording.lock().unwrap().pparams = Some(pparams.clone());
+
+        Ok(pparams)
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        let utxos = self.inner.resolve_input(query).await?;
+
+        let mut recording = self.recording.lock().unwrap();
+        recording.record_utxos(&utxos);
+        Answers::record(&mut recording.answers.inputs, query, &utxos);
+
+        Ok(utxos)
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<UtxoSet, Error> {
+        let utxos = self.inner.resolve_collateral(query).await?;
+
+        let mut recording = self.recording.lock().unwrap();
+        recording.record_utxos(&utxos);
+        Answers::record(&mut recording.answers.collateral, query, &utxos);
+
+        Ok(utxos)
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let datum = self.inner.resolve_datum(hash).await?;
+
+        if let Some(datum) = &datum {
+            let mut recording = self.recording.lock().unwrap();
+
+            if !recording.datums.contains(datum) {
+                recording.datums.push(datum.clone());
+            }
+        }
+
+        Ok(datum)
+    }
+
+    async fn read_utxos(&self, refs: &[UtxoRef]) -> Result<UtxoSet, Error> {
+        let utxos = self.inner.read_utxos(refs).await?;
+        self.recording.lock().unwrap().record_utxos(&utxos);
+
+        Ok(utxos)
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        let tip = self.inner.get_tip().await?;
+        self.recording.lock().unwrap().answers.tip = Some(tip.clone());
+
+        Ok(tip)
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        let config = self.inner.get_slot_config().await?;
+        self.recording.lock().unwrap().answers.slot_config = Some(config.clone());
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tx3_lang::{ArgValue, Protocol};
+
+    use super::*;
+    use crate::{ledgers::file::FileLedger, resolve_tx};
+
+    #[tokio::test]
+    async fn replays_recorded_resolution() {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+
+        let protocol = Protocol::from_file(format!("{manifest_dir}/../../examples/transfer.tx3"))
+            .load()
+            .unwrap();
+
+        let address = pallas::ledger::addresses::Address::from_bech32("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2")
+            .unwrap()
+            .to_vec();
+
+        let tx = protocol
+            .new_tx("transfer")
+            .unwrap()
+            .with_arg("Sender", ArgValue::Address(address.clone()))
+            .with_arg("Receiver", ArgValue::Address(address))
+            .with_arg("quantity", ArgValue::Int(100_000_000))
+            .apply()
+            .unwrap();
+
+        let source = FileLedger::from_file(format!("{manifest_dir}/fixtures/smoke.json")).unwrap();
+
+        let recorder = RecordingLedger::new(source);
+
+        // a reference keeps the recorder available after the resolution
+        let original = resolve_tx(tx.clone(), &recorder, 3).await.unwrap();
+
+        let json = recorder.to_fixture_json().unwrap();
+
+        let fixture: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(fixture["inputs"].as_array().unwrap().len(), 1);
+
+        let replay = FileLedger::from_json(&json).unwrap();
+
+        let replayed = resolve_tx(tx, replay, 3).await.unwrap();
+
+        assert_eq!(original.payload, replayed.payload);
+    }
+}
@@ -0,0 +1,149 @@
+//! Selection of the UTxOs that satisfy the `min_amount` of an input query.
+
+use std::collections::HashMap;
+
+use tx3_lang::ir::{self, InputQuery};
+
+use crate::{coercion, Error};
+
+/// An asset identified by its policy and name. ADA uses an empty policy.
+pub(crate) type AssetKey = (Vec<u8>, Vec<u8>);
+
+fn expr_into_asset_key_part(expr: &ir::Expression) -> Result<Vec<u8>, Error> {
+    match expr {
+        ir::Expression::None => Ok(vec![]),
+        x => Ok(coercion::expr_into_bytes(x)?.to_vec()),
+    }
+}
+
+fn assets_into_amounts(assets: &[ir::AssetExpr]) -> Result<HashMap<AssetKey, u64>, Error> {
+    let mut out = HashMap::new();
+
+    for asset in assets {
+        let policy = expr_into_asset_key_part(&asset.policy)?;
+        let name = expr_into_asset_key_part(&asset.asset_name)?;
+        let amount = coercion::expr_into_number(&asset.amount)?;
+
+        let amount = u64::try_from(amount)
+            .map_err(|_| Error::InvalidAssetExpression(format!("{:?}", asset.amount)))?;
+
+        *out.entry((policy, name)).or_default() += amount;
+    }
+
+    out.retain(|_, amount| *amount > 0);
+
+    Ok(out)
+}
+
+/// Collects the amounts per asset that the `min_amount` of a query requires.
+pub(crate) fn min_amount_requirements(query: &InputQuery) -> Result<HashMap<AssetKey, u64>, Error> {
+    match &query.min_amount {
+        Some(min_amount) => assets_into_amounts(&coercion::expr_into_assets(min_amount)?),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Tells if the UTxO holds nothing but ADA, which is what collateral requires.
+pub(crate) fn is_pure_ada(utxo: &tx3_lang::Utxo) -> bool {
+    utxo.assets.iter().all(|x| x.policy.is_none())
+}
+
+/// Greedy selection of UTxOs that covers the amounts required by a query.
+/// Queries without a `min_amount` are satisfied by any single UTxO.
+pub(crate) struct UtxoSelection {
+    pending: HashMap<AssetKey, u64>,
+    pub selected: Vec<tx3_lang::Utxo>,
+}
+
+impl UtxoSelection {
+    pub fn new(required: HashMap<AssetKey, u64>) -> Self {
+        Self {
+            pending: required,
+            selected: vec![],
+        }
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.pending.is_empty() && !self.selected.is_empty()
+    }
+
+    pub fn offer(&mut self, utxo: tx3_lang::Utxo) -> Result<(), Error> {
+        if self.is_satisfied() {
+            return Ok(());
+        }
+
+        let amounts = assets_into_amounts(&utxo.assets)?;
+
+        let useful =
+            self.pending.is_empty() || self.pending.keys().any(|key| amounts.contains_key(key));
+
+        if !useful {
+            return Ok(());
+        }
+
+        for (key, amount) in amounts {
+            if let Some(pending) = self.pending.get_mut(&key) {
+                *pending = pending.saturating_sub(amount);
+            }
+        }
+
+        self.pending.retain(|_, amount| *amount > 0);
+        self.selected.push(utxo);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lovelace_utxo(index: u32, coin: i128) -> tx3_lang::Utxo {
+        tx3_lang::Utxo {
+            r#ref: tx3_lang::UtxoRef {
+                txid: vec![0xef; 32],
+                index,
+            },
+            address: vec![],
+            datum: None,
+            datum_hash: None,
+            assets: vec![ir::AssetExpr {
+                policy: ir::Expression::None,
+                asset_name: ir::Expression::None,
+                amount: ir::Expression::Number(coin),
+            }],
+            script: None,
+        }
+    }
+
+    #[test]
+    fn selects_until_min_amount_is_covered() {
+        let required = HashMap::from([((vec![], vec![]), 5_000_000)]);
+        let mut selection = UtxoSelection::new(required);
+
+        selection.offer(lovelace_utxo(0, 3_000_000)).unwrap();
+        assert!(!selection.is_satisfied());
+
+        selection.offer(lovelace_utxo(1, 0)).unwrap();
+        selection.offer(lovelace_utxo(2, 3_000_000)).unwrap();
+        assert!(selection.is_satisfied());
+
+        selection.offer(lovelace_utxo(3, 3_000_000)).unwrap();
+
+        let selected: Vec<_> = selection.selected.iter().map(|x| x.r#ref.index).collect();
+
+        assert_eq!(selected, vec![0, 2]);
+    }
+
+    #[test]
+    fn selects_single_utxo_without_min_amount() {
+        let mut selection = UtxoSelection::new(HashMap::new());
+        assert!(!selection.is_satisfied());
+
+        selection.offer(lovelace_utxo(0, 1_000_000)).unwrap();
+        selection.offer(lovelace_utxo(1, 1_000_000)).unwrap();
+
+        assert!(selection.is_satisfied());
+        assert_eq!(selection.selected.len(), 1);
+    }
+}
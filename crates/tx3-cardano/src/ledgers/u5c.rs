@@ -2,10 +2,21 @@ use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
 use tx3_lang::ir::InputQuery;
 
+use pallas::{
+    codec::minicbor,
+    ledger::{
+        addresses::{Address, ShelleyPaymentPart},
+        primitives::{conway as primitives, PlutusData},
+    },
+};
 use tokio::sync::Mutex;
-use utxorpc::CardanoQueryClient;
+use utxorpc::{CardanoQueryClient, CardanoSubmitClient};
 
-use crate::PParams;
+use super::selection::{min_amount_requirements, AssetKey, UtxoSelection};
+use crate::{
+    compile::plutus_data::TryIntoExpr as _, ChainPoint, ExUnitPrices, ExUnits, PParams, Ratio,
+    SlotConfig,
+};
 
 impl From<utxorpc::Error> for crate::Error {
     fn from(error: utxorpc::Error) -> Self {
@@ -18,91 +29,251 @@ pub struct Config {
     pub endpoint_url: String,
     pub api_key: String,
     pub network_id: u8,
+
+    /// Slot config of the network, only required for networks other than
+    /// mainnet since u5c doesn't expose it.
+    #[serde(default)]
+    pub slot_config: Option<SlotConfig>,
 }
 
+/// Number of UTxOs requested on each page when searching by pattern.
+const PAGE_SIZE: u32 = 50;
+
 fn expr_to_address_pattern(
     value: &tx3_lang::ir::Expression,
-) -> utxorpc::spec::cardano::AddressPattern {
+) -> Result<utxorpc::spec::cardano::AddressPattern, crate::Error> {
     let address = match value {
         tx3_lang::ir::Expression::Address(address) => address.clone(),
+        tx3_lang::ir::Expression::Bytes(address) => address.clone(),
         tx3_lang::ir::Expression::String(address) => {
-            pallas::ledger::addresses::Address::from_bech32(address)
-                .unwrap()
-                .to_vec()
+            pallas::ledger::addresses::Address::from_bech32(address)?.to_vec()
+        }
+        _ => {
+            return Err(crate::Error::InvalidAddressExpression(format!(
+                "{:?}",
+                value
+            )))
         }
-        _ => return Default::default(),
     };
 
-    utxorpc::spec::cardano::AddressPattern {
+    Ok(utxorpc::spec::cardano::AddressPattern {
         exact_address: address.into(),
         ..Default::default()
+    })
+}
+
+fn input_query_to_pattern(
+    query: &InputQuery,
+    required: &HashMap<AssetKey, u64>,
+) -> Result<utxorpc::spec::cardano::TxOutputPattern, crate::Error> {
+    let address = query
+        .address
+        .as_ref()
+        .map(expr_to_address_pattern)
+        .transpose()?;
+
+    // the pattern can only match a single asset, the rest of the requirements
+    // are checked while selecting the utxos
+    let asset = required
+        .keys()
+        .filter(|(policy, _)| !policy.is_empty())
+        .min()
+        .map(|(policy, name)| utxorpc::spec::cardano::AssetPattern {
+            policy_id: policy.clone().into(),
+            asset_name: name.clone().into(),
+        });
+
+    if address.is_none() && asset.is_none() {
+        return Err(crate::Error::InputQueryTooBroad);
     }
+
+    Ok(utxorpc::spec::cardano::TxOutputPattern { address, asset })
 }
 
-fn input_query_to_pattern(query: &InputQuery) -> utxorpc::spec::cardano::TxOutputPattern {
-    let address = query.address.as_ref().map(expr_to_address_pattern);
+fn encode_script_ref(script_ref: &primitives::ScriptRef) -> Result<Vec<u8>, crate::Error> {
+    minicbor::to_vec(script_ref)
+        .map_err(|err| crate::Error::MappingError(format!("can't encode script ref: {err}")))
+}
 
-    utxorpc::spec::cardano::TxOutputPattern {
-        address,
-        ..Default::default()
+/// Takes the `ScriptRef` of the original CBOR of an output. Native scripts
+/// keep their raw bytes, so their hash matches the one on chain.
+fn script_ref_from_native(native: &[u8]) -> Result<Option<Vec<u8>>, crate::Error> {
+    let output: primitives::TransactionOutput = minicbor::decode(native)
+        .map_err(|err| crate::Error::MappingError(format!("invalid output cbor: {err}")))?;
+
+    let primitives::TransactionOutput::PostAlonzo(output) = output else {
+        return Ok(None);
+    };
+
+    output
+        .script_ref
+        .as_ref()
+        .map(|x| encode_script_ref(x))
+        .transpose()
+}
+
+/// Maps a u5c script into the CBOR of a `ScriptRef`, which is how reference
+/// scripts are represented in a UTxO. Only used when the original CBOR of
+/// the output isn't available.
+fn script_ref_from_u5c(
+    script: &utxorpc::spec::cardano::Script,
+) -> Result<Option<Vec<u8>>, crate::Error> {
+    use utxorpc::spec::cardano::script::Script;
+
+    let script_ref = match script.script.as_ref() {
+        // re-encoding a native script could change its hash
+        Some(Script::Native(_)) => {
+            return Err(crate::Error::LedgerInternalError(
+                "native script ref without the original cbor of the output".to_string(),
+            ))
+        }
+        Some(Script::PlutusV1(x)) => {
+            primitives::ScriptRef::PlutusV1Script(primitives::PlutusScript(x.to_vec().into()))
+        }
+        Some(Script::PlutusV2(x)) => {
+            primitives::ScriptRef::PlutusV2Script(primitives::PlutusScript(x.to_vec().into()))
+        }
+        Some(Script::PlutusV3(x)) => {
+            primitives::ScriptRef::PlutusV3Script(primitives::PlutusScript(x.to_vec().into()))
+        }
+        None => return Ok(None),
+    };
+
+    Ok(Some(encode_script_ref(&script_ref)?))
+}
+
+fn assets_from_u5c(output: &utxorpc::spec::cardano::TxOutput) -> Vec<tx3_lang::ir::AssetExpr> {
+    let coin = tx3_lang::ir::AssetExpr {
+        policy: tx3_lang::ir::Expression::None,
+        asset_name: tx3_lang::ir::Expression::None,
+        amount: tx3_lang::ir::Expression::Number(output.coin as i128),
+    };
+
+    let multiasset = output.assets.iter().flat_map(|multiasset| {
+        multiasset
+            .assets
+            .iter()
+            .map(|asset| tx3_lang::ir::AssetExpr {
+                policy: tx3_lang::ir::Expression::Bytes(multiasset.policy_id.to_vec()),
+                asset_name: tx3_lang::ir::Expression::Bytes(asset.name.to_vec()),
+                amount: tx3_lang::ir::Expression::Number(asset.output_coin as i128),
+            })
+    });
+
+    std::iter::once(coin).chain(multiasset).collect()
+}
+
+fn inline_datum_from_u5c(
+    datum: &utxorpc::spec::cardano::Datum,
+) -> Result<Option<tx3_lang::ir::Expression>, crate::Error> {
+    // datums carried by hash don't include the original cbor
+    if datum.original_cbor.is_empty() {
+        return Ok(None);
     }
+
+    let data: PlutusData = minicbor::decode(&datum.original_cbor)
+        .map_err(|err| crate::Error::MappingError(format!("invalid datum cbor: {err}")))?;
+
+    Ok(Some(data.try_as_expr()?))
 }
 
-fn utxo_from_u5c_to_tx3(u: utxorpc::ChainUtxo<utxorpc::spec::cardano::TxOutput>) -> tx3_lang::Utxo {
-    tx3_lang::Utxo {
+fn utxo_from_u5c_to_tx3(
+    u: utxorpc::ChainUtxo<utxorpc::spec::cardano::TxOutput>,
+) -> Result<tx3_lang::Utxo, crate::Error> {
+    let txo_ref = u.txo_ref.ok_or(crate::Error::LedgerInternalError(
+        "utxo without ref".to_string(),
+    ))?;
+
+    let parsed = u.parsed.ok_or(crate::Error::LedgerInternalError(
+        "utxo without parsed output".to_string(),
+    ))?;
+
+    let datum = parsed
+        .datum
+        .as_ref()
+        .map(inline_datum_from_u5c)
+        .transpose()?
+        .flatten();
+
+    let datum_hash = parsed
+        .datum
+        .as_ref()
+        .filter(|x| !x.hash.is_empty() && x.original_cbor.is_empty())
+        .map(|x| x.hash.to_vec());
+
+    let script = if u.native.is_empty() {
+        parsed
+            .script
+            .as_ref()
+            .map(script_ref_from_u5c)
+            .transpose()?
+            .flatten()
+    } else {
+        script_ref_from_native(&u.native)?
+    };
+
+    let script = script.map(tx3_lang::ir::Expression::Bytes);
+
+    Ok(tx3_lang::Utxo {
         r#ref: tx3_lang::UtxoRef {
-            txid: u.txo_ref.as_ref().unwrap().hash.clone().into(),
-            index: u.txo_ref.as_ref().unwrap().index,
+            txid: txo_ref.hash.to_vec(),
+            index: txo_ref.index,
         },
-        address: u.parsed.as_ref().unwrap().address.clone().into(),
-        datum: None, //u.parsed.unwrap().datum.into(),
-        assets: vec![tx3_lang::ir::AssetExpr {
-            policy: tx3_lang::ir::Expression::None,
-            asset_name: tx3_lang::ir::Expression::None,
-            amount: tx3_lang::ir::Expression::Number(u.parsed.as_ref().unwrap().coin as i128),
-        }], //u.parsed.unwrap().assets.into(),
-        script: u
-            .parsed
-            .as_ref()
-            .and_then(|x| x.script.as_ref())
-            .and_then(|x| x.script.as_ref())
-            .map(|x| {
-                let mut buf = vec![];
-                x.encode(&mut buf);
-                buf
-            })
-            .map(tx3_lang::ir::Expression::Bytes),
-    }
+        address: parsed.address.to_vec(),
+        datum,
+        datum_hash,
+        assets: assets_from_u5c(&parsed),
+        script,
+    })
+}
+
+/// Collateral must be pure ADA locked by a key, since script-locked outputs
+/// can't be spent without running the script.
+fn is_collateral_candidate(output: &utxorpc::spec::cardano::TxOutput) -> bool {
+    let Ok(Address::Shelley(address)) = Address::from_bytes(&output.address) else {
+        return false;
+    };
+
+    output.assets.is_empty() && matches!(address.payment(), ShelleyPaymentPart::Key(_))
 }
 
 #[derive(Clone)]
 pub struct Ledger {
     queries: Arc<Mutex<utxorpc::CardanoQueryClient>>,
+    submit: Arc<Mutex<utxorpc::CardanoSubmitClient>>,
     network_id: u8,
+    slot_config: Option<SlotConfig>,
 }
 
 impl Ledger {
     pub async fn new(config: Config) -> Result<Self, crate::Error> {
-        let queries = utxorpc::ClientBuilder::new()
+        let builder = utxorpc::ClientBuilder::new()
             .uri(&config.endpoint_url)?
-            .metadata("dmtr-api-key", config.api_key)?
-            .build::<CardanoQueryClient>()
-            .await;
+            .metadata("dmtr-api-key", config.api_key)?;
+
+        let queries = builder.build::<CardanoQueryClient>().await;
+        let submit = builder.build::<CardanoSubmitClient>().await;
 
         Ok(Self {
             queries: Arc::new(Mutex::new(queries)),
+            submit: Arc::new(Mutex::new(submit)),
             network_id: config.network_id,
+            slot_config: config.slot_config,
         })
     }
+}
 
-    // pub async fn read_utxos(
-    //     &mut self,
-    //     refs: Vec<wit::TxoRef>,
-    // ) -> Result<Vec<wit::Utxo>, wit::LedgerError> {
-    //     let refs = refs.into_iter().map(|r| r.into()).collect();
-    //     let utxos = self.queries.read_utxos(refs).await?;
-    //     Ok(utxos.into_iter().map(|u| u.into()).collect())
-    // }
+fn ratio_from_u5c(ratio: Option<&utxorpc::spec::cardano::RationalNumber>) -> Ratio {
+    match ratio {
+        Some(x) => Ratio {
+            numerator: x.numerator.max(0) as u64,
+            denominator: x.denominator as u64,
+        },
+        None => Ratio {
+            numerator: 0,
+            denominator: 1,
+        },
+    }
 }
 
 impl crate::resolve::Ledger for Ledger {
@@ -129,7 +300,7 @@ impl crate::resolve::Ledger for Ledger {
                 coins_per_utxo_byte: params.coins_per_utxo_byte,
                 cost_models: HashMap::from([
                     (
-                        1,
+                        0,
                         params
                             .cost_models
                             .as_ref()
@@ -138,7 +309,7 @@ impl crate::resolve::Ledger for Ledger {
                             .unwrap_or_default(),
                     ),
                     (
-                        2,
+                        1,
                         params
                             .cost_models
                             .as_ref()
@@ -147,7 +318,7 @@ impl crate::resolve::Ledger for Ledger {
                             .unwrap_or_default(),
                     ),
                     (
-                        3,
+                        2,
                         params
                             .cost_models
                             .as_ref()
@@ -156,6 +327,26 @@ impl crate::resolve::Ledger for Ledger {
                             .unwrap_or_default(),
                     ),
                 ]),
+                collateral_percentage: params.collateral_percentage,
+                max_collateral_inputs: params.max_collateral_inputs,
+                max_tx_size: params.max_tx_size,
+                max_value_size: params.max_value_size,
+                execution_prices: ExUnitPrices {
+                    mem: ratio_from_u5c(params.prices.as_ref().and_then(|x| x.memory.as_ref())),
+                    steps: ratio_from_u5c(params.prices.as_ref().and_then(|x| x.steps.as_ref())),
+                },
+                max_tx_ex_units: params
+                    .max_execution_units_per_transaction
+                    .map(|x| ExUnits {
+                        mem: x.memory,
+                        steps: x.steps,
+                    })
+                    .unwrap_or_default(),
+                key_deposit: params.stake_key_deposit,
+                pool_deposit: params.pool_deposit,
+                min_fee_ref_script_cost_per_byte: ratio_from_u5c(
+                    params.min_fee_script_ref_cost_per_byte.as_ref(),
+                ),
             },
         };
 
@@ -163,17 +354,384 @@ impl crate::resolve::Ledger for Ledger {
     }
 
     async fn resolve_input(&self, query: &InputQuery) -> Result<tx3_lang::UtxoSet, crate::Error> {
+        if let Some(refs) = &query.r#ref {
+            let refs = crate::coercion::expr_into_utxo_refs(refs)?;
+            return self.read_utxos(&refs).await;
+        }
+
+        let required = min_amount_requirements(query)?;
+        let pattern = input_query_to_pattern(query, &required)?;
+        let mut selection = UtxoSelection::new(required);
+
+        let mut queries = self.queries.lock().await;
+        let mut start_token = None;
+
+        loop {
+            let page = queries
+                .match_utxos(pattern.clone(), start_token, PAGE_SIZE)
+                .await?;
+
+            for utxo in page.items {
+                selection.offer(utxo_from_u5c_to_tx3(utxo)?)?;
+            }
+
+            if selection.is_satisfied() || page.next.is_none() {
+                break;
+            }
+
+            start_token = page.next;
+        }
+
+        // returning nothing lets the caller report the query as not resolved
+        if !selection.is_satisfied() {
+            return Ok(Default::default());
+        }
+
+        Ok(selection.selected.into_iter().collect())
+    }
+
+    async fn resolve_collateral(
+        &self,
+        query: &InputQuery,
+    ) -> Result<tx3_lang::UtxoSet, crate::Error> {
+        let pattern = input_query_to_pattern(query, &HashMap::new())?;
+
+        let mut queries = self.queries.lock().await;
+        let mut start_token = None;
+        let mut utxos = tx3_lang::UtxoSet::new();
+
+        loop {
+            let page = queries
+                .match_utxos(pattern.clone(), start_token, PAGE_SIZE)
+                .await?;
+
+            for utxo in page.items {
+                if utxo.parsed.as_ref().is_some_and(is_collateral_candidate) {
+                    utxos.insert(utxo_from_u5c_to_tx3(utxo)?);
+                }
+            }
+
+            if page.next.is_none() {
+                break;
+            }
+
+            start_token = page.next;
+        }
+
+        Ok(utxos)
+    }
+
+    async fn read_utxos(
+        &self,
+        refs: &[tx3_lang::UtxoRef],
+    ) -> Result<tx3_lang::UtxoSet, crate::Error> {
+        let refs = refs
+            .iter()
+            .map(|x| utxorpc::spec::query::TxoRef {
+                hash: x.txid.clone().into(),
+                index: x.index,
+            })
+            .collect();
+
         let utxos = self
             .queries
             .lock()
             .await
-            .match_utxos(input_query_to_pattern(query), None, 1)
+            .read_utxos(refs)
             .await?
-            .items
             .into_iter()
             .map(utxo_from_u5c_to_tx3)
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         Ok(utxos)
     }
+
+    async fn get_tip(&self) -> Result<ChainPoint, crate::Error> {
+        let req = utxorpc::spec::query::ReadParamsRequest::default();
+
+        let res = self
+            .queries
+            .lock()
+            .await
+            .read_params(req)
+            .await
+            .map_err(|err| crate::Error::LedgerInternalError(format!("{:?}", err)))?;
+
+        let tip = res
+            .into_inner()
+            .ledger_tip
+            .ok_or(crate::Error::LedgerInternalError(
+                "unexpected response from read_params".to_string(),
+            ))?;
+
+        Ok(ChainPoint {
+            slot: tip.slot,
+            hash: tip.hash.to_vec(),
+        })
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, crate::Error> {
+        if let Some(config) = &self.slot_config {
+            return Ok(config.clone());
+        }
+
+        match crate::Network::try_from(self.network_id) {
+            Ok(crate::Network::Mainnet) => Ok(SlotConfig::mainnet()),
+            _ => Err(crate::Error::LedgerInternalError(format!(
+                "no slot config for network {}",
+                self.network_id
+            ))),
+        }
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, crate::Error> {
+        let req = utxorpc::spec::query::ReadDataRequest {
+            keys: vec![hash.to_vec().into()],
+            ..Default::default()
+        };
+
+        let res = self
+            .queries
+            .lock()
+            .await
+            .read_data(req)
+            .await
+            .map_err(|err| crate::Error::LedgerInternalError(format!("{:?}", err)))?;
+
+        let datum = res
+            .into_inner()
+            .values
+            .into_iter()
+            .find(|x| x.key.as_ref() == hash && !x.native_bytes.is_empty())
+            .map(|x| x.native_bytes.to_vec());
+
+        Ok(datum)
+    }
+}
+
+impl crate::submit::Submitter for Ledger {
+    async fn submit_tx(&self, payload: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let refs = self
+            .submit
+            .lock()
+            .await
+            .submit_tx(vec![payload.to_vec()])
+            .await?;
+
+        let hash = refs
+            .into_iter()
+            .next()
+            .ok_or(crate::Error::LedgerInternalError(
+                "unexpected response from submit_tx".to_string(),
+            ))?;
+
+        Ok(hash.to_vec())
+    }
+
+    async fn wait_for_confirmation(&self, hash: &[u8]) -> Result<(), crate::Error> {
+        let mut events = self
+            .submit
+            .lock()
+            .await
+            .wait_for_tx(vec![hash.to_vec()])
+            .await?;
+
+        while let Some(event) = events.event().await? {
+            if event.stage == utxorpc::spec::submit::Stage::Confirmed {
+                return Ok(());
+            }
+        }
+
+        Err(crate::Error::LedgerInternalError(
+            "tx stream closed before confirmation".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utxorpc::spec::cardano as u5c;
+
+    use super::*;
+    use crate::compile::plutus_data::{constr, IntoData as _};
+
+    #[test]
+    fn maps_full_utxo() {
+        let datum = constr(0, vec![42u64.as_data()]);
+
+        let output = u5c::TxOutput {
+            address: vec![0x61; 29].into(),
+            coin: 5_000_000,
+            assets: vec![u5c::Multiasset {
+                policy_id: vec![0xab; 28].into(),
+                assets: vec![u5c::Asset {
+                    name: b"MYTOKEN".to_vec().into(),
+                    output_coin: 10,
+                    mint_coin: 0,
+                }],
+                redeemer: None,
+            }],
+            datum: Some(u5c::Datum {
+                hash: vec![0xcd; 32].into(),
+                payload: None,
+                original_cbor: minicbor::to_vec(&datum).unwrap().into(),
+            }),
+            script: Some(u5c::Script {
+                script: Some(u5c::script::Script::PlutusV3(vec![0x01, 0x02].into())),
+            }),
+        };
+
+        let utxo = utxo_from_u5c_to_tx3(utxorpc::ChainUtxo {
+            parsed: Some(output),
+            native: Default::default(),
+            txo_ref: Some(utxorpc::spec::query::TxoRef {
+                hash: vec![0xef; 32].into(),
+                index: 1,
+            }),
+        })
+        .unwrap();
+
+        assert_eq!(utxo.r#ref.index, 1);
+        assert_eq!(utxo.assets.len(), 2);
+        assert_eq!(
+            utxo.assets[1].policy,
+            tx3_lang::ir::Expression::Bytes(vec![0xab; 28])
+        );
+        assert_eq!(utxo.assets[1].amount, tx3_lang::ir::Expression::Number(10));
+
+        assert_eq!(
+            utxo.datum,
+            Some(tx3_lang::ir::Expression::Struct(tx3_lang::ir::StructExpr {
+                constructor: 0,
+                fields: vec![tx3_lang::ir::Expression::Number(42)],
+            }))
+        );
+        assert_eq!(utxo.datum_hash, None);
+
+        let Some(tx3_lang::ir::Expression::Bytes(script)) = utxo.script else {
+            panic!("missing script ref");
+        };
+
+        let script: primitives::ScriptRef = minicbor::decode(&script).unwrap();
+        assert!(matches!(script, primitives::ScriptRef::PlutusV3Script(_)));
+    }
+
+    #[test]
+    fn keeps_native_script_bytes() {
+        // a `ScriptAll` with an indefinite-length list, which re-encoding
+        // would turn into a definite one
+        let script_ref = [0x82, 0x00, 0x82, 0x01, 0x9f, 0xff];
+
+        let mut native = minicbor::Encoder::new(vec![]);
+        native
+            .map(3)
+            .unwrap()
+            .u8(0)
+            .unwrap()
+            .bytes(&[0x61; 29])
+            .unwrap()
+            .u8(1)
+            .unwrap()
+            .u64(5_000_000)
+            .unwrap()
+            .u8(3)
+            .unwrap()
+            .tag(minicbor::data::Tag::new(24))
+            .unwrap()
+            .bytes(&script_ref)
+            .unwrap();
+
+        let utxo = utxo_from_u5c_to_tx3(utxorpc::ChainUtxo {
+            parsed: Some(Default::default()),
+            native: native.into_writer().into(),
+            txo_ref: Some(Default::default()),
+        })
+        .unwrap();
+
+        assert_eq!(
+            utxo.script,
+            Some(tx3_lang::ir::Expression::Bytes(script_ref.to_vec()))
+        );
+    }
+
+    #[test]
+    fn fails_on_missing_ref() {
+        let result = utxo_from_u5c_to_tx3(utxorpc::ChainUtxo {
+            parsed: Some(Default::default()),
+            native: Default::default(),
+            txo_ref: None,
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn picks_pure_ada_key_outputs_as_collateral() {
+        let key_locked = u5c::TxOutput {
+            address: vec![0x61; 29].into(),
+            coin: 5_000_000,
+            ..Default::default()
+        };
+
+        assert!(is_collateral_candidate(&key_locked));
+
+        let script_locked = u5c::TxOutput {
+            address: [vec![0x71], vec![0xab; 28]].concat().into(),
+            ..key_locked.clone()
+        };
+
+        assert!(!is_collateral_candidate(&script_locked));
+
+        let with_tokens = u5c::TxOutput {
+            assets: vec![Default::default()],
+            ..key_locked.clone()
+        };
+
+        assert!(!is_collateral_candidate(&with_tokens));
+    }
+
+    #[test]
+    fn builds_asset_pattern_from_min_amount() {
+        let query = InputQuery {
+            address: Some(tx3_lang::ir::Expression::Address(vec![0x61; 29])),
+            min_amount: Some(tx3_lang::ir::Expression::Assets(vec![
+                tx3_lang::ir::AssetExpr {
+                    policy: tx3_lang::ir::Expression::None,
+                    asset_name: tx3_lang::ir::Expression::None,
+                    amount: tx3_lang::ir::Expression::Number(2_000_000),
+                },
+                tx3_lang::ir::AssetExpr {
+                    policy: tx3_lang::ir::Expression::Hash(vec![0xab; 28]),
+                    asset_name: tx3_lang::ir::Expression::String("MYTOKEN".to_string()),
+                    amount: tx3_lang::ir::Expression::Number(10),
+                },
+            ])),
+            r#ref: None,
+        };
+
+        let required = min_amount_requirements(&query).unwrap();
+        assert_eq!(required.get(&(vec![], vec![])), Some(&2_000_000));
+
+        let pattern = input_query_to_pattern(&query, &required).unwrap();
+        let asset = pattern.asset.unwrap();
+        assert_eq!(asset.policy_id.as_ref(), &[0xab; 28]);
+        assert_eq!(asset.asset_name.as_ref(), b"MYTOKEN");
+        assert!(pattern.address.is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_address() {
+        let query = InputQuery {
+            address: Some(tx3_lang::ir::Expression::String(
+                "not_an_address".to_string(),
+            )),
+            ..Default::default()
+        };
+
+        let result = input_query_to_pattern(&query, &HashMap::new());
+        assert!(matches!(result, Err(crate::Error::InvalidAddress(_))));
+
+        let result = input_query_to_pattern(&InputQuery::default(), &HashMap::new());
+        assert!(matches!(result, Err(crate::Error::InputQueryTooBroad)));
+    }
 }
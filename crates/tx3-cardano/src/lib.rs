@@ -3,7 +3,11 @@ use std::collections::HashMap;
 pub mod coercion;
 pub mod compile;
 pub mod ledgers;
+#[cfg(feature = "phase2")]
+pub mod phase2;
 pub mod resolve;
+pub mod submit;
+pub mod validate;
 
 // Re-export pallas for upstream users
 pub use pallas;
@@ -84,20 +88,170 @@ pub enum Error {
 
     #[error("can't compile non-constant TIR")]
     CantCompileNonConstantTir,
+
+    #[error("output can't have both an inline datum and a datum hash")]
+    ConflictingDatum,
+
+    #[error("datum with hash {0} not found")]
+    DatumNotFound(String),
+
+    #[error("can't determine the script language of {0}")]
+    UnknownScriptLanguage(String),
+
+    #[error("script of policy '{0}' doesn't match its hash")]
+    ScriptHashMismatch(String),
+
+    #[error("missing cost model for plutus version {0}")]
+    MissingCostModel(PlutusVersion),
+
+    #[error("not enough collateral, {0} lovelace required")]
+    InsufficientCollateral(u64),
+
+    #[error("input {0} doesn't exist or was already spent")]
+    InputNotFound(String),
+
+    #[error("invalid ledger fixture: {0}")]
+    InvalidFixture(String),
+
+    #[error("tx size of {0} bytes exceeds the maximum of {1}")]
+    TxTooLarge(u64, u64),
+
+    #[error("tx execution units {0:?} exceed the maximum of {1:?}")]
+    ExUnitsTooLarge(ExUnits, ExUnits),
+
+    #[error("tx {0} wasn't confirmed in time")]
+    ConfirmationTimeout(String),
+
+    #[error("invalid tx: {0}")]
+    InvalidTx(#[from] validate::ValidationError),
+
+    #[cfg(feature = "phase2")]
+    #[error("script evaluation failed: {0}")]
+    ScriptEvalFailed(String),
+
+    #[cfg(not(feature = "phase2"))]
+    #[error("tx runs scripts, their execution units can't be evaluated without the phase2 feature")]
+    ScriptEvalUnavailable,
 }
 
 pub type Network = pallas::ledger::primitives::NetworkId;
+/// Plutus language as keyed by the ledger: 0 for V1, 1 for V2 and 2 for V3.
 pub type PlutusVersion = u8;
 pub type CostModel = Vec<i64>;
 
+#[derive(Debug, Clone)]
 pub struct PParams {
     pub network: Network,
     pub min_fee_coefficient: u64,
     pub min_fee_constant: u64,
     pub coins_per_utxo_byte: u64,
     pub cost_models: HashMap<PlutusVersion, CostModel>,
+    pub collateral_percentage: u64,
+    pub max_collateral_inputs: u64,
+    pub max_tx_size: u64,
+    pub max_value_size: u64,
+    pub execution_prices: ExUnitPrices,
+    pub max_tx_ex_units: ExUnits,
+    pub key_deposit: u64,
+    pub pool_deposit: u64,
+    pub min_fee_ref_script_cost_per_byte: Ratio,
+}
+
+/// A non-negative rational number, as used by the ledger for prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Ratio {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+impl Ratio {
+    /// Multiplies the ratio by an integer, rounding up like the ledger does
+    /// when computing fees.
+    pub fn mul_ceil(&self, value: u64) -> u64 {
+        if self.denominator == 0 {
+            return 0;
+        }
+
+        let out = (value as u128 * self.numerator as u128).div_ceil(self.denominator as u128);
+        u64::try_from(out).unwrap_or(u64::MAX)
+    }
+}
+
+/// Memory and cpu steps consumed by the execution of plutus scripts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExUnits {
+    pub mem: u64,
+    pub steps: u64,
+}
+
+/// Price in lovelace of each unit of memory and cpu steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExUnitPrices {
+    pub mem: Ratio,
+    pub steps: Ratio,
+}
+
+impl ExUnitPrices {
+    pub fn fee(&self, units: &ExUnits) -> u64 {
+        self.mem.mul_ceil(units.mem) + self.steps.mul_ceil(units.steps)
+    }
+}
+
+/// A point in the chain, identified by its slot and block hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainPoint {
+    pub slot: u64,
+    pub hash: Vec<u8>,
+}
+
+/// Parameters to convert between slots and wall-clock time. Times are unix
+/// timestamps in milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SlotConfig {
+    pub zero_time: u64,
+    pub zero_slot: u64,
+    pub slot_length: u64,
+}
+
+impl SlotConfig {
+    pub fn mainnet() -> Self {
+        Self {
+            zero_time: 1596059091000,
+            zero_slot: 4492800,
+            slot_length: 1000,
+        }
+    }
+
+    pub fn preprod() -> Self {
+        Self {
+            zero_time: 1655769600000,
+            zero_slot: 86400,
+            slot_length: 1000,
+        }
+    }
+
+    pub fn preview() -> Self {
+        Self {
+            zero_time: 1666656000000,
+            zero_slot: 0,
+            slot_length: 1000,
+        }
+    }
+
+    pub fn slot_to_time(&self, slot: u64) -> u64 {
+        let elapsed = slot.saturating_sub(self.zero_slot) * self.slot_length;
+        self.zero_time + elapsed
+    }
+
+    pub fn time_to_slot(&self, time: u64) -> u64 {
+        let elapsed = time.saturating_sub(self.zero_time) / self.slot_length;
+        self.zero_slot + elapsed
+    }
 }
 
 pub use compile::compile_tx;
-pub use resolve::resolve_tx;
 pub use resolve::Ledger;
+pub use resolve::{
+    resolve_tx, resolve_tx_with_options, resolve_tx_with_progress, ResolveEvent, ResolveOptions,
+};
+pub use submit::{resolve_and_submit, Submitter};
@@ -0,0 +1,149 @@
+//! Phase-2 evaluation of compiled transactions.
+//!
+//! Runs the plutus scripts of the tx through pallas' uplc evaluator to find
+//! out the execution units each redeemer consumes, so that they can be set in
+//! the redeemers instead of the placeholders used while compiling.
+
+use std::collections::BTreeMap;
+
+use pallas::ledger::{
+    primitives::{conway as primitives, Hash},
+    traverse::{Era, MultiEraTx},
+    validate::{
+        phase2::{evaluate_tx, script_context},
+        utils::{EraCbor, MultiEraProtocolParameters, TxoRef, UtxoMap},
+    },
+};
+use tx3_lang::Utxo;
+
+use crate::{compile, validate, Error, ExUnits, PParams, SlotConfig};
+
+/// Evaluates the redeemers of a compiled tx, returning the units consumed by
+/// each of them keyed by tag and index. The `utxos` are the ones resolved for
+/// its inputs, collateral and reference inputs.
+pub fn eval_redeemers(
+    payload: &[u8],
+    utxos: &[Utxo],
+    pparams: &PParams,
+    slot_config: &SlotConfig,
+) -> Result<BTreeMap<(primitives::RedeemerTag, u32), ExUnits>, Error> {
+    let tx = MultiEraTx::decode_for_era(Era::Conway, payload)
+        .map_err(|err| Error::MappingError(format!("can't decode compiled tx: {err}")))?;
+
+    let mut map = UtxoMap::new();
+
+    for utxo in utxos {
+        let output = compile::utxo_into_output(utxo)?;
+
+        let cbor = pallas::codec::minicbor::to_vec(&output)
+            .map_err(|err| Error::MappingError(format!("can't encode output: {err}")))?;
+
+        let r#ref = TxoRef(Hash::from(utxo.r#ref.txid.as_slice()), utxo.r#ref.index);
+        map.insert(r#ref, EraCbor(Era::Conway, cbor));
+    }
+
+    let pparams = MultiEraProtocolParameters::Conway(validate::prot_params(pparams));
+
+    let slot_config = script_context::SlotConfig {
+        slot_length: slot_config.slot_length,
+        zero_slot: slot_config.zero_slot,
+        zero_time: slot_config.zero_time,
+    };
+
+    let report = evaluate_tx(&tx, &pparams, &map, &slot_config)
+        .map_err(|err| Error::ScriptEvalFailed(err.to_string()))?;
+
+    let units = report
+        .into_iter()
+        .map(|x| {
+            let units = ExUnits {
+                mem: x.units.mem,
+                steps: x.units.steps,
+            };
+
+            ((x.tag, x.index), units)
+        })
+        .collect();
+
+    Ok(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use pallas::ledger::addresses::Address;
+    use tx3_lang::{ArgValue, Protocol};
+
+    use super::*;
+    use crate::{ledgers::file::FileLedger, resolve_tx};
+
+    const ADDRESS: &str = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+    #[tokio::test]
+    async fn sets_evaluated_units_in_redeemers() {
+        let protocol = Protocol::from_string(
+            r#"
+            policy AlwaysTrue {
+                hash: 0xf1a2e2f94a40c7b4a18ccb7c02c54d82be3e4418abb4c61f78b5b38e,
+                script: 0x46450101002499,
+                language: plutus_v3,
+            }
+
+            asset MyToken = AlwaysTrue."MYTOKEN";
+
+            party Requester;
+
+            tx mint_token(quantity: Int) {
+                input source {
+                    from: Requester,
+                    min_amount: fees,
+                }
+
+                mint {
+                    amount: MyToken(quantity),
+                    redeemer: (),
+                }
+
+                output {
+                    to: Requester,
+                    amount: source - fees + MyToken(quantity),
+                }
+            }
+            "#
+            .to_string(),
+        )
+        .load()
+        .unwrap();
+
+        let address = Address::from_bech32(ADDRESS).unwrap().to_vec();
+
+        let tx = protocol
+            .new_tx("mint_token")
+            .unwrap()
+            .with_arg("Requester", ArgValue::Address(address))
+            .with_arg("quantity", ArgValue::Int(1))
+            .apply()
+            .unwrap();
+
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let ledger = FileLedger::from_file(format!("{manifest_dir}/fixtures/smoke.json")).unwrap();
+
+        let eval = resolve_tx(tx, ledger, 3).await.unwrap();
+
+        let tx: primitives::Tx = pallas::codec::minicbor::decode(&eval.payload).unwrap();
+
+        let Some(primitives::Redeemers::List(redeemers)) =
+            tx.transaction_witness_set.redeemer.as_deref()
+        else {
+            panic!("expected a list of redeemers");
+        };
+
+        let units = &redeemers[0].ex_units;
+
+        // the placeholder set while compiling is replaced by what the script used
+        assert_ne!((units.mem, units.steps), (2000, 200000));
+        assert!(units.mem > 0 && units.steps > 0);
+
+        assert_eq!(eval.ex_units.mem, units.mem);
+        assert_eq!(eval.ex_units.steps, units.steps);
+    }
+}
@@ -1,13 +1,44 @@
-use pallas::ledger::primitives::conway as primitives;
+use std::{collections::HashSet, future::Future};
+
+use pallas::ledger::{
+    addresses::{Address, ShelleyPaymentPart},
+    primitives::conway as primitives,
+};
 use tx3_lang::{applying::Apply, ir::InputQuery};
 
-use crate::{compile::compile_tx, Error, PParams};
+use crate::{
+    compile::{compile_tx, scripts, Context},
+    ledgers::{cached::CachedLedger, selection::is_pure_ada},
+    validate, ChainPoint, Error, ExUnits, PParams, SlotConfig,
+};
 
 #[derive(Debug, Default)]
 pub struct TxEval {
     pub payload: Vec<u8>,
     pub fee: u64,
-    pub ex_units: u64,
+    pub ex_units: ExUnits,
+}
+
+/// A step of the resolution of a tx, reported as it happens so that callers
+/// can show progress during the ledger round-trips.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ResolveEvent {
+    /// The ledger is being queried for the UTxOs of an input.
+    QueryIssued { input: String, query: InputQuery },
+
+    /// The UTxOs the ledger selected for an input.
+    InputsSelected {
+        input: String,
+        utxos: Vec<tx3_lang::UtxoRef>,
+    },
+
+    /// The tx is compiled again paying the fees required by the previous
+    /// round.
+    FeeRound { round: usize, fees: u64 },
+
+    /// The tx was compiled, requiring the given fees.
+    Compiled { fee: u64, size: usize },
 }
 
 #[trait_variant::make(Send)]
@@ -15,98 +46,486 @@ pub trait Ledger {
     async fn get_pparams(&self) -> Result<PParams, Error>;
 
     async fn resolve_input(&self, query: &InputQuery) -> Result<tx3_lang::UtxoSet, Error>;
+
+    /// Resolves the UTxOs matching the query that can be used as collateral,
+    /// which are the ones holding nothing but ADA. Defaults to the UTxOs
+    /// resolved for the query as an input, leaving out the ones with tokens.
+    fn resolve_collateral(
+        &self,
+        query: &InputQuery,
+    ) -> impl Future<Output = Result<tx3_lang::UtxoSet, Error>> {
+        let utxos = self.resolve_input(query);
+
+        async move { Ok(utxos.await?.into_iter().filter(is_pure_ada).collect()) }
+    }
+
+    /// Looks up the original CBOR of a datum by its hash. Defaults to
+    /// knowing no datums.
+    fn resolve_datum(&self, _hash: &[u8]) -> impl Future<Output = Result<Option<Vec<u8>>, Error>> {
+        async { Ok(None) }
+    }
+
+    /// Reads the UTxOs pointed by the given references. References that don't
+    /// exist in the ledger are left out of the result, so the default reads
+    /// none.
+    fn read_utxos(
+        &self,
+        _refs: &[tx3_lang::UtxoRef],
+    ) -> impl Future<Output = Result<tx3_lang::UtxoSet, Error>> {
+        async { Ok(tx3_lang::UtxoSet::new()) }
+    }
+
+    /// Returns the point of the chain the ledger is currently at.
+    async fn get_tip(&self) -> Result<ChainPoint, Error>;
+
+    /// Returns the config used to convert between slots and wall-clock time.
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error>;
 }
 
-fn eval_size_fees(tx: &[u8], pparams: &PParams) -> Result<u64, Error> {
-    Ok(tx.len() as u64 * pparams.min_fee_coefficient + pparams.min_fee_constant + 200_000)
+impl<T: Ledger + Sync> Ledger for &T {
+    async fn get_pparams(&self) -> Result<PParams, Error> {
+        (**self).get_pparams().await
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<tx3_lang::UtxoSet, Error> {
+        (**self).resolve_input(query).await
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<tx3_lang::UtxoSet, Error> {
+        (**self).resolve_collateral(query).await
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        (**self).resolve_datum(hash).await
+    }
+
+    async fn read_utxos(&self, refs: &[tx3_lang::UtxoRef]) -> Result<tx3_lang::UtxoSet, Error> {
+        (**self).read_utxos(refs).await
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, Error> {
+        (**self).get_tip().await
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+        (**self).get_slot_config().await
+    }
 }
 
-#[allow(dead_code)]
-fn eval_redeemer_fees(_tx: &primitives::Tx, _pparams: &PParams) -> Result<u64, Error> {
-    // pallas::ledger::validate::phase_two::evaluate_tx(tx.into(), pparams, utxos,
-    // slot_config);
-    todo!()
+/// Collects the UTxOs the tx points at without spending them: the ones in
+/// `reference` blocks and the ones holding the scripts of its policies.
+fn referenced_utxos(tx: &tx3_lang::ir::Tx) -> Vec<tx3_lang::UtxoRef> {
+    let references = tx.references.iter();
+
+    let policies = scripts::tx_policies(tx)
+        .filter_map(|x| x.script.as_ref())
+        .map(|x| match x {
+            tx3_lang::ir::ScriptSource::UtxoRef { r#ref, .. } => r#ref,
+            tx3_lang::ir::ScriptSource::Embedded(x) => x,
+        });
+
+    let mut out = vec![];
+
+    for expr in references.chain(policies) {
+        if let tx3_lang::ir::Expression::UtxoRefs(refs) = expr {
+            for r#ref in refs {
+                if !out.contains(r#ref) {
+                    out.push(r#ref.clone());
+                }
+            }
+        }
+    }
+
+    out
 }
 
-async fn eval_pass<L: Ledger>(
+/// Size of a single vkey witness: a CBOR array with a 32 bytes key and a 64
+/// bytes signature.
+const VKEY_WITNESS_SIZE: u64 = 101;
+
+/// Bytes taken by the witness set entry that holds the vkey witnesses: the map
+/// key, the set tag and the array header.
+const VKEY_WITNESS_OVERHEAD: u64 = 7;
+
+/// Reference scripts are priced in tiers of this size, each one more expensive
+/// than the previous.
+const REF_SCRIPT_TIER_SIZE: u128 = 25_600;
+
+/// Estimates the number of vkey witnesses that will sign the tx: one per
+/// distinct payment key of the spent and collateral inputs, plus one per
+/// required signer.
+fn estimate_vkey_witnesses(tx: &primitives::Tx, spent: &[tx3_lang::Utxo]) -> u64 {
+    let mut keys = HashSet::new();
+
+    for utxo in spent {
+        match Address::from_bytes(&utxo.address) {
+            Ok(Address::Shelley(x)) => {
+                if let ShelleyPaymentPart::Key(hash) = x.payment() {
+                    keys.insert(hash.to_vec());
+                }
+            }
+            // bootstrap witnesses are signed by the key behind the whole address
+            Ok(Address::Byron(_)) => {
+                keys.insert(utxo.address.clone());
+            }
+            _ => (),
+        }
+    }
+
+    for signer in tx.transaction_body.required_signers.iter().flatten() {
+        keys.insert(signer.to_vec());
+    }
+
+    keys.len() as u64
+}
+
+fn eval_size_fees(tx: &[u8], witnesses: u64, pparams: &PParams) -> Result<u64, Error> {
+    let mut size = tx.len() as u64;
+
+    if witnesses > 0 {
+        size += VKEY_WITNESS_OVERHEAD + witnesses * VKEY_WITNESS_SIZE;
+    }
+
+    if size > pparams.max_tx_size {
+        return Err(Error::TxTooLarge(size, pparams.max_tx_size));
+    }
+
+    Ok(size * pparams.min_fee_coefficient + pparams.min_fee_constant)
+}
+
+/// Computes the Conway fee for the reference scripts of the spent and
+/// reference inputs. Each tier of 25KiB costs 1.2 times the previous one and
+/// only the final sum is rounded down.
+fn eval_ref_script_fees(scripts: &[&tx3_lang::Utxo], pparams: &PParams) -> u64 {
+    let size: u128 = scripts
+        .iter()
+        .filter_map(|x| match &x.script {
+            Some(tx3_lang::ir::Expression::Bytes(script)) => Some(script.len() as u128),
+            _ => None,
+        })
+        .sum();
+
+    let price = pparams.min_fee_ref_script_cost_per_byte;
+
+    if price.denominator == 0 {
+        return 0;
+    }
+
+    // the fee so far is kept as a fraction over the price of the current tier
+    let mut numerator = price.numerator as u128;
+    let mut denominator = price.denominator as u128;
+    let mut acc = 0u128;
+    let mut remaining = size;
+
+    while remaining > 0 {
+        let chunk = remaining.min(REF_SCRIPT_TIER_SIZE);
+        acc += chunk * numerator;
+        remaining -= chunk;
+
+        numerator *= 6;
+        denominator *= 5;
+        acc *= 5;
+    }
+
+    let fee = if size > 0 { acc / denominator } else { 0 };
+
+    u64::try_from(fee).unwrap_or(u64::MAX)
+}
+
+fn sum_ex_units(tx: &primitives::Tx) -> ExUnits {
+    let mut units = ExUnits::default();
+
+    let mut add = |x: &primitives::ExUnits| {
+        units.mem += x.mem;
+        units.steps += x.steps;
+    };
+
+    match tx.transaction_witness_set.redeemer.as_deref() {
+        Some(primitives::Redeemers::List(list)) => list.iter().for_each(|x| add(&x.ex_units)),
+        Some(primitives::Redeemers::Map(map)) => map.iter().for_each(|(_, x)| add(&x.ex_units)),
+        None => (),
+    }
+
+    units
+}
+
+fn eval_redeemer_fees(units: ExUnits, pparams: &PParams) -> Result<u64, Error> {
+    let max = pparams.max_tx_ex_units;
+
+    if units.mem > max.mem || units.steps > max.steps {
+        return Err(Error::ExUnitsTooLarge(units, max));
+    }
+
+    Ok(pparams.execution_prices.fee(&units))
+}
+
+fn same_ref(utxo: &tx3_lang::Utxo, input: &primitives::TransactionInput) -> bool {
+    utxo.r#ref.txid == input.transaction_id.as_slice() && utxo.r#ref.index as u64 == input.index
+}
+
+/// The outcome of compiling the tx with a given amount of fees.
+struct Pass {
+    eval: TxEval,
+    tx: tx3_lang::ir::Tx,
+    spent: Vec<tx3_lang::Utxo>,
+    references: Vec<tx3_lang::Utxo>,
+}
+
+async fn eval_pass<L: Ledger + Sync, P: Fn(ResolveEvent)>(
     tx: &tx3_lang::ProtoTx,
     pparams: &PParams,
-    ledger: &L,
+    ledger: &CachedLedger<L>,
     best_fees: u64,
-) -> Result<Option<TxEval>, Error> {
+    progress: &P,
+) -> Result<Pass, Error> {
     let mut attempt = tx.clone();
     attempt.set_fees(best_fees);
 
     attempt = attempt.apply()?;
 
+    let mut context = Context::default();
+    let mut spent = vec![];
+
     for (name, query) in attempt.find_queries() {
+        // later passes are mostly answered by the cache, so only the queries
+        // that actually reach the ledger are reported
+        if !ledger.has_input(&query) {
+            progress(ResolveEvent::QueryIssued {
+                input: name.clone(),
+                query: query.clone(),
+            });
+        }
+
         let utxos = ledger.resolve_input(&query).await?;
 
         if utxos.is_empty() {
             return Err(Error::InputsNotResolved(name, Box::new(query)));
         }
 
+        progress(ResolveEvent::InputsSelected {
+            input: name.clone(),
+            utxos: utxos.iter().map(|x| x.r#ref.clone()).collect(),
+        });
+
+        for hash in utxos.iter().filter_map(|x| x.datum_hash.as_ref()) {
+            let datum = ledger
+                .resolve_datum(hash)
+                .await?
+                .ok_or_else(|| Error::DatumNotFound(hex::encode(hash)))?;
+
+            context.input_datums.push(datum);
+        }
+
+        spent.extend(utxos.iter().cloned());
         attempt.set_input(&name, utxos);
     }
 
+    for collateral in attempt.as_ref().collateral.iter() {
+        if !ledger.has_collateral(&collateral.query) {
+            progress(ResolveEvent::QueryIssued {
+                input: "collateral".to_string(),
+                query: collateral.query.clone(),
+            });
+        }
+
+        let utxos = ledger.resolve_collateral(&collateral.query).await?;
+
+        if utxos.is_empty() {
+            return Err(Error::InputsNotResolved(
+                "collateral".to_string(),
+                Box::new(collateral.query.clone()),
+            ));
+        }
+
+        progress(ResolveEvent::InputsSelected {
+            input: "collateral".to_string(),
+            utxos: utxos.iter().map(|x| x.r#ref.clone()).collect(),
+        });
+
+        for utxo in utxos {
+            if !context.collateral.iter().any(|x| x.r#ref == utxo.r#ref) {
+                context.collateral.push(utxo);
+            }
+        }
+    }
+
+    let references = referenced_utxos(attempt.as_ref());
+
+    if !references.is_empty() {
+        context.references = ledger.read_utxos(&references).await?.into_iter().collect();
+    }
+
     let attempt = attempt.apply()?;
 
     if !attempt.as_ref().is_constant() {
         return Err(Error::CantCompileNonConstantTir);
     }
 
-    let tx = compile_tx(attempt.as_ref(), pparams)?;
+    let tx = compile_tx(attempt.as_ref(), pparams, &context)?;
+
+    context
+        .collateral
+        .iter()
+        .for_each(|x| spent.push(x.clone()));
+
+    let has_redeemers = tx.transaction_witness_set.redeemer.is_some();
+
+    // the placeholder units set while compiling don't reflect what the scripts
+    // consume, so neither the tx nor its fees would hold up on chain
+    #[cfg(not(feature = "phase2"))]
+    if has_redeemers {
+        return Err(Error::ScriptEvalUnavailable);
+    }
+
+    // the scripts run over the tx with placeholder units, which is then compiled
+    // again with the units they actually consumed
+    #[cfg(feature = "phase2")]
+    let tx = if has_redeemers {
+        let payload = pallas::codec::minicbor::to_vec(&tx).unwrap();
+        let utxos: Vec<_> = spent.iter().chain(&context.references).cloned().collect();
+        let slot_config = ledger.get_slot_config().await?;
+
+        context.ex_units = crate::phase2::eval_redeemers(&payload, &utxos, pparams, &slot_config)?;
+
+        compile_tx(attempt.as_ref(), pparams, &context)?
+    } else {
+        tx
+    };
 
     let payload = pallas::codec::minicbor::to_vec(&tx).unwrap();
 
-    let size_fees = eval_size_fees(&payload, pparams)?;
+    let witnesses = estimate_vkey_witnesses(&tx, &spent);
+    let size_fees = eval_size_fees(&payload, witnesses, pparams)?;
+
+    let ex_units = sum_ex_units(&tx);
+    let redeemer_fees = eval_redeemer_fees(ex_units, pparams)?;
+
+    let with_scripts: Vec<_> = spent
+        .iter()
+        .filter(|x| tx.transaction_body.inputs.iter().any(|i| same_ref(x, i)))
+        .chain(context.references.iter())
+        .collect();
 
-    //let redeemer_fees = eval_redeemer_fees(tx, pparams)?;
+    let ref_script_fees = eval_ref_script_fees(&with_scripts, pparams);
 
     let eval = TxEval {
         payload,
-        fee: size_fees, // TODO: add redeemer fees
-        ex_units: 0,
+        fee: size_fees + redeemer_fees + ref_script_fees,
+        ex_units,
     };
 
-    if eval.fee != best_fees {
-        return Ok(Some(eval));
-    }
+    progress(ResolveEvent::Compiled {
+        fee: eval.fee,
+        size: eval.payload.len(),
+    });
 
-    Ok(None)
+    Ok(Pass {
+        eval,
+        tx: attempt.as_ref().clone(),
+        spent,
+        references: context.references,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolveOptions {
+    /// How many times the tx is compiled again while its fees keep changing.
+    pub max_optimize_rounds: usize,
+    /// Runs the phase-1 ledger checks over the resolved tx.
+    pub validate: bool,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            max_optimize_rounds: 3,
+            validate: false,
+        }
+    }
 }
 
-pub async fn resolve_tx<T: Ledger>(
+pub async fn resolve_tx<T: Ledger + Sync>(
     tx: tx3_lang::ProtoTx,
     ledger: T,
     max_optimize_rounds: usize,
 ) -> Result<TxEval, Error> {
+    let options = ResolveOptions {
+        max_optimize_rounds,
+        ..Default::default()
+    };
+
+    resolve_tx_with_options(tx, ledger, &options).await
+}
+
+pub async fn resolve_tx_with_options<T: Ledger + Sync>(
+    tx: tx3_lang::ProtoTx,
+    ledger: T,
+    options: &ResolveOptions,
+) -> Result<TxEval, Error> {
+    resolve_tx_with_progress(tx, ledger, options, |_| {}).await
+}
+
+/// Resolves the tx, calling `progress` with each step of the resolution as
+/// it happens.
+pub async fn resolve_tx_with_progress<T, P>(
+    tx: tx3_lang::ProtoTx,
+    ledger: T,
+    options: &ResolveOptions,
+    progress: P,
+) -> Result<TxEval, Error>
+where
+    T: Ledger + Sync,
+    P: Fn(ResolveEvent) + Send + Sync,
+{
+    // each pass queries the same inputs again until the fees converge
+    let ledger = CachedLedger::new(ledger);
+
     let pparams = ledger.get_pparams().await?;
-    let mut last_eval = TxEval::default();
+    let mut fees = 0;
     let mut rounds = 0;
 
     // one initial pass to reduce any available params;
     let tx = tx.apply()?;
 
-    while let Some(better) = eval_pass(&tx, &pparams, &ledger, last_eval.fee).await? {
-        last_eval = better;
+    let mut pass = eval_pass(&tx, &pparams, &ledger, fees, &progress).await?;
 
-        if rounds > max_optimize_rounds {
+    // the fees paid by the payload have to cover the ones it requires
+    while pass.eval.fee > fees {
+        if rounds > options.max_optimize_rounds {
             return Err(Error::MaxOptimizeRoundsReached);
         }
 
+        fees = pass.eval.fee;
         rounds += 1;
+
+        progress(ResolveEvent::FeeRound {
+            round: rounds,
+            fees,
+        });
+
+        pass = eval_pass(&tx, &pparams, &ledger, fees, &progress).await?;
+    }
+
+    if options.validate {
+        let tip = ledger.get_tip().await?;
+        let utxos: Vec<_> = pass.spent.iter().chain(&pass.references).cloned().collect();
+        validate::validate_tx(&pass.tx, &pass.eval.payload, &utxos, &pparams, tip.slot)?;
     }
 
-    Ok(last_eval)
+    Ok(TxEval {
+        fee: fees,
+        ..pass.eval
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use tx3_lang::{ArgValue, Protocol};
 
+    use pallas::ledger::traverse::ComputeHash;
+
     use super::*;
-    use crate::ledgers::mock::MockLedger;
+    use crate::ledgers::file::FileLedger;
 
     fn load_protocol(example_name: &str) -> Protocol {
         let manifest_dir = env!("CARGO_MANIFEST_DIR");
@@ -114,6 +533,11 @@ mod tests {
         Protocol::from_file(&code).load().unwrap()
     }
 
+    fn load_fixture(fixture_name: &str) -> FileLedger {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        FileLedger::from_file(format!("{manifest_dir}/fixtures/{fixture_name}.json")).unwrap()
+    }
+
     fn address_to_bytes(address: &str) -> ArgValue {
         ArgValue::Address(
             pallas::ledger::addresses::Address::from_bech32(address)
@@ -134,12 +558,69 @@ mod tests {
             .apply()
             .unwrap();
 
-        let tx = resolve_tx(tx, MockLedger, 3).await.unwrap();
+        let ledger = load_fixture("smoke");
+        let pparams = ledger.get_pparams().await.unwrap();
+
+        let tx = resolve_tx(tx, ledger, 3).await.unwrap();
+
+        // a single sender signs the tx, so one witness is added to the payload
+        let size = tx.payload.len() as u64 + VKEY_WITNESS_OVERHEAD + VKEY_WITNESS_SIZE;
+        assert_eq!(
+            tx.fee,
+            size * pparams.min_fee_coefficient + pparams.min_fee_constant
+        );
 
         println!("{}", hex::encode(tx.payload));
         println!("{}", tx.fee);
     }
 
+    #[tokio::test]
+    async fn reports_resolve_progress() {
+        let protocol = load_protocol("transfer");
+
+        let tx = protocol.new_tx("transfer")
+            .unwrap()
+            .with_arg("Sender", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("Receiver", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("quantity", ArgValue::Int(100_000_000))
+            .apply()
+            .unwrap();
+
+        let events = std::sync::Mutex::new(vec![]);
+
+        let tx =
+            resolve_tx_with_progress(tx, load_fixture("smoke"), &ResolveOptions::default(), |x| {
+                events.lock().unwrap().push(x)
+            })
+            .await
+            .unwrap();
+
+        let events = events.into_inner().unwrap();
+
+        assert!(matches!(
+            &events[0],
+            ResolveEvent::QueryIssued { input, .. } if input == "source"
+        ));
+        assert!(matches!(
+            &events[1],
+            ResolveEvent::InputsSelected { utxos, .. } if !utxos.is_empty()
+        ));
+        assert!(events
+            .iter()
+            .any(|x| matches!(x, ResolveEvent::FeeRound { round: 1, .. })));
+
+        // the following passes hit the cache, so the query is reported once
+        let queries = events
+            .iter()
+            .filter(|x| matches!(x, ResolveEvent::QueryIssued { .. }))
+            .count();
+        assert_eq!(queries, 1);
+        assert!(matches!(
+            events.last(),
+            Some(ResolveEvent::Compiled { fee, .. }) if *fee == tx.fee
+        ));
+    }
+
     #[tokio::test]
     async fn smoke_test_vesting() {
         let protocol = load_protocol("vesting");
@@ -153,7 +634,7 @@ mod tests {
             .apply()
             .unwrap();
 
-        let tx = resolve_tx(tx, MockLedger, 3).await.unwrap();
+        let tx = resolve_tx(tx, load_fixture("smoke"), 3).await.unwrap();
 
         println!("{}", hex::encode(tx.payload));
         println!("{}", tx.fee);
@@ -180,10 +661,295 @@ mod tests {
         dbg!(&tx.find_params());
         dbg!(&tx.find_queries());
 
-        let tx = resolve_tx(tx, MockLedger, 3).await.unwrap();
+        // the protocol only knows the hash of its validator, so its units
+        // can't be evaluated and the tx is refused instead of under-priced
+        let result = resolve_tx(tx, load_fixture("smoke"), 3).await;
+        assert!(result.is_err());
+    }
 
-        println!("{}", hex::encode(tx.payload));
-        println!("{}", tx.fee);
+    #[tokio::test]
+    async fn datum_hash_output_test() {
+        let protocol = Protocol::from_string(
+            r#"
+            party Sender;
+            party Receiver;
+
+            tx lock(quantity: Int) {
+                input source {
+                    from: Sender,
+                    min_amount: Ada(quantity) + fees,
+                }
+
+                output {
+                    to: Receiver,
+                    amount: Ada(quantity),
+                    datum_hash: 0xAFAFAF,
+                }
+
+                output {
+                    to: Sender,
+                    amount: source - Ada(quantity) - fees,
+                }
+            }
+            "#
+            .to_string(),
+        )
+        .load()
+        .unwrap();
+
+        let tx = protocol.new_tx("lock")
+            .unwrap()
+            .with_arg("Sender", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("Receiver", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("quantity", ArgValue::Int(100_000_000))
+            .apply()
+            .unwrap();
+
+        let tx = resolve_tx(tx, load_fixture("smoke"), 3).await.unwrap();
+
+        let tx: primitives::Tx = pallas::codec::minicbor::decode(&tx.payload).unwrap();
+
+        let datums = tx.transaction_witness_set.plutus_data.as_ref().unwrap();
+        assert_eq!(datums.len(), 1);
+
+        let primitives::TransactionOutput::PostAlonzo(output) = &tx.transaction_body.outputs[0]
+        else {
+            panic!("expected post-alonzo output");
+        };
+
+        let Some(primitives::DatumOption::Hash(hash)) = output.datum_option.as_deref() else {
+            panic!("expected datum hash");
+        };
+
+        assert_eq!(*hash, datums[0].compute_hash());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "phase2"), ignore = "the script runs only with phase2")]
+    async fn embedded_script_mint_test() {
+        let protocol = Protocol::from_string(
+            r#"
+            policy AlwaysTrue {
+                hash: 0xf1a2e2f94a40c7b4a18ccb7c02c54d82be3e4418abb4c61f78b5b38e,
+                script: 0x46450101002499,
+                language: plutus_v3,
+            }
+
+            asset MyToken = AlwaysTrue."MYTOKEN";
+
+            party Requester;
+
+            tx mint_token(quantity: Int) {
+                input source {
+                    from: Requester,
+                    min_amount: fees,
+                }
+
+                mint {
+                    amount: MyToken(quantity),
+                    redeemer: (),
+                }
+
+                output {
+                    to: Requester,
+                    amount: source - fees + MyToken(quantity),
+                }
+            }
+            "#
+            .to_string(),
+        )
+        .load()
+        .unwrap();
+
+        let tx = protocol.new_tx("mint_token")
+            .unwrap()
+            .with_arg("Requester", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("quantity", ArgValue::Int(1))
+            .apply()
+            .unwrap();
+
+        let tx = resolve_tx(tx, load_fixture("smoke"), 3).await.unwrap();
+
+        let tx: primitives::Tx = pallas::codec::minicbor::decode(&tx.payload).unwrap();
+
+        let scripts = tx
+            .transaction_witness_set
+            .plutus_v3_script
+            .as_ref()
+            .unwrap();
+        assert_eq!(scripts.len(), 1);
+        assert!(tx.transaction_witness_set.plutus_v1_script.is_none());
+        assert!(tx.transaction_witness_set.plutus_v2_script.is_none());
+
+        let (policy, _) = tx
+            .transaction_body
+            .mint
+            .as_ref()
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(*policy, scripts[0].compute_hash());
+
+        // with a single language, the integrity hash matches the one computed
+        // by pallas for the plutus v3 language view
+        let pparams = load_fixture("smoke").get_pparams().await.unwrap();
+
+        let expected = primitives::ScriptData {
+            redeemers: tx
+                .transaction_witness_set
+                .redeemer
+                .as_ref()
+                .unwrap()
+                .to_owned()
+                .unwrap(),
+            datums: None,
+            language_view: primitives::LanguageView(2, pparams.cost_models[&2].clone()),
+        };
+
+        assert_eq!(tx.transaction_body.script_data_hash, Some(expected.hash()));
+    }
+
+    #[tokio::test]
+    async fn native_policy_mint_test() {
+        let protocol = Protocol::from_string(
+            r#"
+            party Minter;
+
+            native_policy MultiSig = all {
+                sig(Minter),
+                after(1000),
+            };
+
+            asset MyToken = MultiSig."MYTOKEN";
+
+            tx mint_token(quantity: Int) {
+                input source {
+                    from: Minter,
+                    min_amount: fees,
+                }
+
+                mint {
+                    amount: MyToken(quantity),
+                }
+
+                output {
+                    to: Minter,
+                    amount: source - fees + MyToken(quantity),
+                }
+            }
+            "#
+            .to_string(),
+        )
+        .load()
+        .unwrap();
+
+        let minter = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+        let tx = protocol
+            .new_tx("mint_token")
+            .unwrap()
+            .with_arg("Minter", address_to_bytes(minter))
+            .with_arg("quantity", ArgValue::Int(1))
+            .apply()
+            .unwrap();
+
+        let tx = resolve_tx(tx, load_fixture("smoke"), 3).await.unwrap();
+
+        let tx: primitives::Tx = pallas::codec::minicbor::decode(&tx.payload).unwrap();
+
+        let scripts = tx.transaction_witness_set.native_script.as_ref().unwrap();
+        assert_eq!(scripts.len(), 1);
+        assert!(tx.transaction_witness_set.redeemer.is_none());
+
+        let (policy, _) = tx
+            .transaction_body
+            .mint
+            .as_ref()
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(*policy, scripts[0].compute_hash());
+
+        let pallas::ledger::addresses::Address::Shelley(minter) =
+            pallas::ledger::addresses::Address::from_bech32(minter).unwrap()
+        else {
+            unreachable!();
+        };
+
+        let signers = tx.transaction_body.required_signers.as_ref().unwrap();
+        assert_eq!(
+            signers.iter().collect::<Vec<_>>(),
+            vec![minter.payment().as_hash()]
+        );
+        assert_eq!(tx.transaction_body.validity_interval_start, Some(1000));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "phase2"), ignore = "the script runs only with phase2")]
+    async fn collateral_return_test() {
+        let protocol = Protocol::from_string(
+            r#"
+            policy AlwaysTrue {
+                hash: 0xf1a2e2f94a40c7b4a18ccb7c02c54d82be3e4418abb4c61f78b5b38e,
+                script: 0x46450101002499,
+                language: plutus_v3,
+            }
+
+            asset MyToken = AlwaysTrue."MYTOKEN";
+
+            party Requester;
+
+            tx mint_token(quantity: Int) {
+                input source {
+                    from: Requester,
+                    min_amount: fees,
+                }
+
+                collateral {
+                    from: Requester,
+                }
+
+                mint {
+                    amount: MyToken(quantity),
+                    redeemer: (),
+                }
+
+                output {
+                    to: Requester,
+                    amount: source - fees + MyToken(quantity),
+                }
+            }
+            "#
+            .to_string(),
+        )
+        .load()
+        .unwrap();
+
+        let tx = protocol.new_tx("mint_token")
+            .unwrap()
+            .with_arg("Requester", address_to_bytes("addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2"))
+            .with_arg("quantity", ArgValue::Int(1))
+            .apply()
+            .unwrap();
+
+        let tx = resolve_tx(tx, load_fixture("smoke"), 3).await.unwrap();
+
+        let tx: primitives::Tx = pallas::codec::minicbor::decode(&tx.payload).unwrap();
+        let body = &tx.transaction_body;
+
+        assert_eq!(body.collateral.as_ref().unwrap().len(), 1);
+
+        let total = body.total_collateral.unwrap();
+        assert_eq!(total, (body.fee * 150).div_ceil(100));
+
+        let Some(primitives::TransactionOutput::PostAlonzo(r#return)) = &body.collateral_return
+        else {
+            panic!("missing collateral return");
+        };
+
+        assert_eq!(r#return.value, primitives::Value::Coin(500_000_000 - total));
     }
 
     #[tokio::test]
@@ -202,9 +968,131 @@ mod tests {
 
         dbg!(&tx.find_params());
 
-        let tx = resolve_tx(tx, MockLedger, 3).await.unwrap();
+        // the protocol only knows the hash of its policy, so its units can't
+        // be evaluated
+        let result = resolve_tx(tx, load_fixture("smoke"), 3).await;
 
-        println!("{}", hex::encode(&tx.payload));
-        println!("{}", tx.fee);
+        #[cfg(not(feature = "phase2"))]
+        assert!(matches!(result, Err(Error::ScriptEvalUnavailable)));
+
+        #[cfg(feature = "phase2")]
+        assert!(matches!(result, Err(Error::ScriptEvalFailed(_))));
+    }
+
+    #[test]
+    fn size_fees_respect_max_tx_size() {
+        let pparams = PParams {
+            max_tx_size: 200,
+            ..crate::ledgers::mock::pparams()
+        };
+
+        // 50 bytes of payload plus the overhead and a single witness
+        assert_eq!(eval_size_fees(&[0; 50], 1, &pparams).unwrap(), 158 + 2);
+
+        let err = eval_size_fees(&[0; 100], 1, &pparams).unwrap_err();
+        assert!(matches!(err, Error::TxTooLarge(208, 200)));
+    }
+
+    #[test]
+    fn ref_script_fees_are_tiered() {
+        let pparams = crate::ledgers::mock::pparams();
+
+        let utxo = |size: usize| tx3_lang::Utxo {
+            r#ref: tx3_lang::UtxoRef {
+                txid: vec![0; 32],
+                index: 0,
+            },
+            address: vec![],
+            datum: None,
+            datum_hash: None,
+            assets: vec![],
+            script: Some(tx3_lang::ir::Expression::Bytes(vec![0; size])),
+        };
+
+        let small = utxo(1_000);
+        assert_eq!(eval_ref_script_fees(&[&small], &pparams), 15_000);
+
+        // a full tier at 15 per byte plus 10000 bytes at 18 per byte
+        let large = utxo(35_600);
+        assert_eq!(eval_ref_script_fees(&[&large], &pparams), 384_000 + 180_000);
+
+        assert_eq!(eval_ref_script_fees(&[], &pparams), 0);
+    }
+
+    #[test]
+    fn execution_prices_round_up() {
+        let prices = crate::ledgers::mock::pparams().execution_prices;
+
+        let units = ExUnits {
+            mem: 2000,
+            steps: 200000,
+        };
+
+        // 2000 * 0.0577 = 115.4 and 200000 * 0.0000721 = 14.42
+        assert_eq!(prices.fee(&units), 116 + 15);
+    }
+
+    /// A ledger implementing only the required methods, holding a UTxO with
+    /// ADA and another with tokens.
+    struct MinimalLedger;
+
+    fn minimal_utxo(index: u32, policy: tx3_lang::ir::Expression) -> tx3_lang::Utxo {
+        tx3_lang::Utxo {
+            r#ref: tx3_lang::UtxoRef {
+                txid: vec![0; 32],
+                index,
+            },
+            address: vec![0x61; 29],
+            datum: None,
+            datum_hash: None,
+            assets: vec![tx3_lang::ir::AssetExpr {
+                policy,
+                asset_name: tx3_lang::ir::Expression::None,
+                amount: tx3_lang::ir::Expression::Number(5_000_000),
+            }],
+            script: None,
+        }
+    }
+
+    impl Ledger for MinimalLedger {
+        async fn get_pparams(&self) -> Result<PParams, Error> {
+            Ok(crate::ledgers::mock::pparams())
+        }
+
+        async fn resolve_input(&self, _query: &InputQuery) -> Result<tx3_lang::UtxoSet, Error> {
+            Ok(tx3_lang::UtxoSet::from([
+                minimal_utxo(0, tx3_lang::ir::Expression::None),
+                minimal_utxo(1, tx3_lang::ir::Expression::Hash(vec![0xab; 28])),
+            ]))
+        }
+
+        async fn get_tip(&self) -> Result<ChainPoint, Error> {
+            Ok(ChainPoint {
+                slot: 0,
+                hash: vec![0; 32],
+            })
+        }
+
+        async fn get_slot_config(&self) -> Result<SlotConfig, Error> {
+            Ok(SlotConfig::mainnet())
+        }
+    }
+
+    #[tokio::test]
+    async fn ledger_defaults() {
+        let ledger = MinimalLedger;
+
+        let collateral = ledger
+            .resolve_collateral(&InputQuery::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            collateral,
+            tx3_lang::UtxoSet::from([minimal_utxo(0, tx3_lang::ir::Expression::None)])
+        );
+
+        assert_eq!(ledger.resolve_datum(&[0; 32]).await.unwrap(), None);
+        assert!(ledger.read_utxos(&[]).await.unwrap().is_empty());
     }
 }
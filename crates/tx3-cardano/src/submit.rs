@@ -0,0 +1,112 @@
+//! Submission of resolved transactions to the chain.
+
+use std::time::Duration;
+
+use crate::{
+    resolve::{resolve_tx_with_options, Ledger, ResolveOptions, TxEval},
+    Error,
+};
+
+#[trait_variant::make(Send)]
+pub trait Submitter {
+    /// Submits the CBOR of a signed tx, returning its hash.
+    async fn submit_tx(&self, payload: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// Waits until the tx with the given hash is confirmed on chain.
+    async fn wait_for_confirmation(&self, hash: &[u8]) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SubmitOptions {
+    pub resolve: ResolveOptions,
+    /// How long to wait for the tx to be confirmed. The submission returns as
+    /// soon as the tx is accepted when not set.
+    pub confirmation_timeout: Option<Duration>,
+}
+
+/// Resolves a tx, signs its payload with `sign` and submits it, returning the
+/// hash of the submitted tx.
+pub async fn resolve_and_submit<L, S, F>(
+    tx: tx3_lang::ProtoTx,
+    ledger: L,
+    submitter: &S,
+    sign: F,
+    options: &SubmitOptions,
+) -> Result<Vec<u8>, Error>
+where
+    L: Ledger + Sync,
+    S: Submitter + Sync,
+    F: FnOnce(&TxEval) -> Result<Vec<u8>, Error>,
+{
+    let eval = resolve_tx_with_options(tx, ledger, &options.resolve).await?;
+
+    let signed = sign(&eval)?;
+
+    let hash = submitter.submit_tx(&signed).await?;
+
+    if let Some(timeout) = options.confirmation_timeout {
+        tokio::time::timeout(timeout, submitter.wait_for_confirmation(&hash))
+            .await
+            .map_err(|_| Error::ConfirmationTimeout(hex::encode(&hash)))??;
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use tx3_lang::{ArgValue, Protocol};
+
+    use super::*;
+    use crate::ledgers::emulator::EmulatorLedger;
+
+    const ADDRESS: &str = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+    #[tokio::test]
+    async fn submits_to_emulator() {
+        let address = pallas::ledger::addresses::Address::from_bech32(ADDRESS)
+            .unwrap()
+            .to_vec();
+
+        let ledger = EmulatorLedger::default();
+        let funds = ledger.fund(&address, 500_000_000);
+
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+
+        let protocol = Protocol::from_file(format!("{manifest_dir}/../../examples/transfer.tx3"))
+            .load()
+            .unwrap();
+
+        let tx = protocol
+            .new_tx("transfer")
+            .unwrap()
+            .with_arg("Sender", ArgValue::Address(address.clone()))
+            .with_arg("Receiver", ArgValue::Address(address))
+            .with_arg("quantity", ArgValue::Int(100_000_000))
+            .apply()
+            .unwrap();
+
+        let options = SubmitOptions {
+            confirmation_timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        // the emulator doesn't check witnesses, the payload can go unsigned
+        let hash = resolve_and_submit(
+            tx,
+            ledger.clone(),
+            &ledger,
+            |x| Ok(x.payload.clone()),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        let utxos = ledger.utxos();
+        assert!(utxos.iter().all(|x| x.r#ref != funds));
+        assert!(utxos.iter().all(|x| x.r#ref.txid == hash));
+
+        let unknown = ledger.wait_for_confirmation(&[0; 32]).await;
+        assert!(matches!(unknown, Err(Error::TxNotFound(_))));
+    }
+}
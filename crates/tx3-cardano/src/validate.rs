@@ -0,0 +1,479 @@
+//! Phase-1 validation of compiled transactions.
+//!
+//! These are the checks the ledger runs before executing any script, as
+//! implemented by pallas' conway rules, applied to the unsigned tx right after
+//! it's compiled. Each error points back to the block of the tx template that
+//! caused it, so that the issue can be reported in terms of the protocol
+//! instead of the raw tx.
+//!
+//! The tx isn't signed yet, so missing or wrong signatures are not reported.
+//! The ledger checks the witnesses before the languages, the auxiliary data and
+//! the script data hash, so those are left to the node on submission.
+
+use pallas::{
+    codec::minicbor,
+    ledger::{
+        addresses::Address,
+        primitives::{alonzo, conway as primitives, RationalNumber, TransactionInput},
+        traverse::{MultiEraInput, MultiEraOutput},
+        validate::{
+            phase1::conway::validate_conway_tx,
+            utils::{
+                conway_get_val_size_in_words, get_lovelace_from_conway_val, ConwayProtParams,
+                PostAlonzoError, UTxOs, ValidationError as LedgerError,
+            },
+        },
+    },
+};
+use tx3_lang::{ir, Utxo, UtxoRef};
+
+use crate::{compile, Error, Network, PParams, Ratio};
+
+/// The block of a tx template that a validation error refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxBlock {
+    Tx,
+    Input(String),
+    Output(usize),
+    Collateral,
+    Validity,
+}
+
+impl std::fmt::Display for TxBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxBlock::Tx => write!(f, "tx"),
+            TxBlock::Input(name) => write!(f, "input '{name}'"),
+            TxBlock::Output(index) => write!(f, "output #{index}"),
+            TxBlock::Collateral => write!(f, "collateral"),
+            TxBlock::Validity => write!(f, "validity"),
+        }
+    }
+}
+
+/// A ledger rule broken by the tx, along with the block that broke it.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{block}: {error}")]
+pub struct ValidationError {
+    block: TxBlock,
+    #[source]
+    error: LedgerError,
+}
+
+impl ValidationError {
+    /// The block of the tx template that caused the error.
+    pub fn block(&self) -> TxBlock {
+        self.block.clone()
+    }
+
+    /// The ledger rule that was broken.
+    pub fn error(&self) -> &LedgerError {
+        &self.error
+    }
+}
+
+// network magics of the public networks, the rules only look at them to pick
+// the languages and cost models available at a given slot
+const MAINNET_MAGIC: u32 = 764824073;
+const PREVIEW_MAGIC: u32 = 2;
+
+fn same_ref(r#ref: &UtxoRef, input: &TransactionInput) -> bool {
+    r#ref.txid == input.transaction_id.as_slice() && r#ref.index as u64 == input.index
+}
+
+fn input_block(tx: &ir::Tx, input: &TransactionInput) -> TxBlock {
+    tx.inputs
+        .iter()
+        .find(|x| x.refs.iter().any(|r| same_ref(r, input)))
+        .map(|x| TxBlock::Input(x.name.clone()))
+        .unwrap_or(TxBlock::Tx)
+}
+
+fn network_id(network: Network) -> u8 {
+    match network {
+        Network::Mainnet => 1,
+        Network::Testnet => 0,
+    }
+}
+
+fn rational(ratio: &Ratio) -> RationalNumber {
+    RationalNumber {
+        numerator: ratio.numerator,
+        denominator: ratio.denominator,
+    }
+}
+
+fn narrow(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+/// Maps the params known to tx3 into the ones expected by the conway rules.
+/// The ones that only matter for governance or block production are left
+/// empty, phase-1 validation of a tx never looks at them.
+pub(crate) fn prot_params(pparams: &PParams) -> ConwayProtParams {
+    let zero = RationalNumber {
+        numerator: 0,
+        denominator: 1,
+    };
+
+    let cost_model = |version| pparams.cost_models.get(&version).cloned();
+
+    ConwayProtParams {
+        system_start: Default::default(),
+        epoch_length: 0,
+        slot_length: 0,
+        minfee_a: narrow(pparams.min_fee_coefficient),
+        minfee_b: narrow(pparams.min_fee_constant),
+        max_block_body_size: 0,
+        max_transaction_size: narrow(pparams.max_tx_size),
+        max_block_header_size: 0,
+        key_deposit: pparams.key_deposit,
+        pool_deposit: pparams.pool_deposit,
+        desired_number_of_stake_pools: 0,
+        protocol_version: (10, 0),
+        min_pool_cost: 0,
+        ada_per_utxo_byte: pparams.coins_per_utxo_byte,
+        cost_models_for_script_languages: primitives::CostModels {
+            plutus_v1: cost_model(0),
+            plutus_v2: cost_model(1),
+            plutus_v3: cost_model(2),
+            unknown: Default::default(),
+        },
+        execution_costs: alonzo::ExUnitPrices {
+            mem_price: rational(&pparams.execution_prices.mem),
+            step_price: rational(&pparams.execution_prices.steps),
+        },
+        max_tx_ex_units: alonzo::ExUnits {
+            mem: pparams.max_tx_ex_units.mem,
+            steps: pparams.max_tx_ex_units.steps,
+        },
+        max_block_ex_units: alonzo::ExUnits {
+            mem: pparams.max_tx_ex_units.mem,
+            steps: pparams.max_tx_ex_units.steps,
+        },
+        max_value_size: narrow(pparams.max_value_size),
+        collateral_percentage: narrow(pparams.collateral_percentage),
+        max_collateral_inputs: narrow(pparams.max_collateral_inputs),
+        expansion_rate: zero.clone(),
+        treasury_growth_rate: zero.clone(),
+        maximum_epoch: 0,
+        pool_pledge_influence: zero.clone(),
+        pool_voting_thresholds: primitives::PoolVotingThresholds {
+            motion_no_confidence: zero.clone(),
+            committee_normal: zero.clone(),
+            committee_no_confidence: zero.clone(),
+            hard_fork_initiation: zero.clone(),
+            security_voting_threshold: zero.clone(),
+        },
+        drep_voting_thresholds: primitives::DRepVotingThresholds {
+            motion_no_confidence: zero.clone(),
+            committee_normal: zero.clone(),
+            committee_no_confidence: zero.clone(),
+            update_constitution: zero.clone(),
+            hard_fork_initiation: zero.clone(),
+            pp_network_group: zero.clone(),
+            pp_economic_group: zero.clone(),
+            pp_technical_group: zero.clone(),
+            pp_governance_group: zero.clone(),
+            treasury_withdrawal: zero.clone(),
+        },
+        min_committee_size: 0,
+        committee_term_limit: 0,
+        governance_action_validity_period: 0,
+        governance_action_deposit: 0,
+        drep_deposit: 0,
+        drep_inactivity_period: 0,
+        minfee_refscript_cost_per_byte: rational(&pparams.min_fee_ref_script_cost_per_byte),
+    }
+}
+
+fn output_value<'a>(output: &'a primitives::TransactionOutput) -> Option<&'a primitives::Value> {
+    match output {
+        primitives::TransactionOutput::PostAlonzo(x) => Some(&x.value),
+        primitives::TransactionOutput::Legacy(_) => None,
+    }
+}
+
+fn output_address<'a>(output: &'a primitives::TransactionOutput) -> &'a [u8] {
+    match output {
+        primitives::TransactionOutput::PostAlonzo(x) => &x.address,
+        primitives::TransactionOutput::Legacy(x) => &x.address,
+    }
+}
+
+/// Finds the output that breaks an output-level rule, replaying the same
+/// condition the rule checks.
+fn offending_output(
+    body: &primitives::TransactionBody,
+    error: &PostAlonzoError,
+    prot_pps: &ConwayProtParams,
+    network_id: u8,
+) -> Option<usize> {
+    body.outputs.iter().position(|output| match error {
+        PostAlonzoError::MinLovelaceUnreached => output_value(output).is_some_and(|x| {
+            let words = conway_get_val_size_in_words(x);
+            get_lovelace_from_conway_val(x) < prot_pps.ada_per_utxo_byte * (words + 160)
+        }),
+        PostAlonzoError::MaxValSizeExceeded => output_value(output)
+            .is_some_and(|x| conway_get_val_size_in_words(x) > prot_pps.max_value_size as u64),
+        PostAlonzoError::OutputWrongNetworkID => {
+            match Address::from_bytes(output_address(output)) {
+                Ok(Address::Shelley(x)) => x.network().value() != network_id,
+                _ => false,
+            }
+        }
+        _ => false,
+    })
+}
+
+fn error_block(
+    tx: &ir::Tx,
+    body: &primitives::TransactionBody,
+    utxos: &UTxOs,
+    error: &LedgerError,
+    prot_pps: &ConwayProtParams,
+    network_id: u8,
+) -> TxBlock {
+    let LedgerError::PostAlonzo(error) = error else {
+        return TxBlock::Tx;
+    };
+
+    match error {
+        PostAlonzoError::InputNotInUTxO => body
+            .inputs
+            .iter()
+            .find(|x| !utxos.contains_key(&MultiEraInput::from_alonzo_compatible(x)))
+            .map(|x| input_block(tx, x))
+            .unwrap_or(TxBlock::Tx),
+        PostAlonzoError::CollateralNotInUTxO
+        | PostAlonzoError::CollateralMissing
+        | PostAlonzoError::TooManyCollaterals
+        | PostAlonzoError::CollateralNotVKeyLocked
+        | PostAlonzoError::CollateralMinLovelace
+        | PostAlonzoError::NonLovelaceCollateral
+        | PostAlonzoError::CollateralWrongAssets
+        | PostAlonzoError::CollateralAnnotation => TxBlock::Collateral,
+        PostAlonzoError::BlockPrecedesValInt | PostAlonzoError::BlockExceedsValInt => {
+            TxBlock::Validity
+        }
+        PostAlonzoError::MinLovelaceUnreached
+        | PostAlonzoError::MaxValSizeExceeded
+        | PostAlonzoError::OutputWrongNetworkID => {
+            offending_output(body, error, prot_pps, network_id)
+                .map(TxBlock::Output)
+                .unwrap_or(TxBlock::Tx)
+        }
+        _ => TxBlock::Tx,
+    }
+}
+
+/// Errors the ledger raises for the witnesses that only signing provides.
+fn is_unsigned_error(error: &LedgerError) -> bool {
+    matches!(
+        error,
+        LedgerError::PostAlonzo(
+            PostAlonzoError::VKWitnessMissing
+                | PostAlonzoError::VKWrongSignature
+                | PostAlonzoError::ReqSignerMissing
+                | PostAlonzoError::ReqSignerWrongSig
+        )
+    )
+}
+
+/// Runs the phase-1 checks over a compiled tx. The `utxos` are the ones
+/// resolved for its inputs, collateral and reference inputs, and `slot` is the
+/// current tip of the chain.
+pub fn validate_tx(
+    tx: &ir::Tx,
+    payload: &[u8],
+    utxos: &[Utxo],
+    pparams: &PParams,
+    slot: u64,
+) -> Result<(), Error> {
+    let compiled: primitives::Tx = minicbor::decode(payload)
+        .map_err(|err| Error::MappingError(format!("can't decode compiled tx: {err}")))?;
+
+    let outputs = utxos
+        .iter()
+        .map(|x| {
+            Ok((
+                compile::utxo_ref_into_input(&x.r#ref),
+                compile::utxo_into_output(x)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let utxos: UTxOs = outputs
+        .iter()
+        .map(|(input, output)| {
+            (
+                MultiEraInput::from_alonzo_compatible(input),
+                MultiEraOutput::from_conway(output),
+            )
+        })
+        .collect();
+
+    let prot_pps = prot_params(pparams);
+    let network_id = network_id(pparams.network);
+
+    let magic = match pparams.network {
+        Network::Mainnet => MAINNET_MAGIC,
+        Network::Testnet => PREVIEW_MAGIC,
+    };
+
+    let result = validate_conway_tx(&compiled, &utxos, &prot_pps, &slot, &magic, &network_id);
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(error) if is_unsigned_error(&error) => Ok(()),
+        Err(error) => {
+            let body = &compiled.transaction_body;
+            let block = error_block(tx, body, &utxos, &error, &prot_pps, network_id);
+
+            Err(ValidationError { block, error }.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tx3_lang::{ArgValue, Protocol};
+
+    use super::*;
+    use crate::{ledgers::emulator::EmulatorLedger, resolve_tx_with_options, ResolveOptions};
+
+    // the emulator runs as a testnet, so outputs to mainnet addresses are rejected
+    const ADDRESS: &str = "addr_test1qz0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6qllhhm4";
+
+    const PROTOCOL: &str = r#"
+        party Sender;
+
+        party Receiver;
+
+        tx transfer(quantity: Int) {
+            input source {
+                from: Sender,
+                min_amount: Ada(quantity),
+            }
+
+            output {
+                to: Receiver,
+                amount: Ada(quantity),
+            }
+
+            output {
+                to: Sender,
+                amount: source - Ada(quantity) - fees,
+            }
+        }
+
+        tx unbalanced(quantity: Int) {
+            input source {
+                from: Sender,
+                min_amount: Ada(quantity),
+            }
+
+            output {
+                to: Receiver,
+                amount: Ada(quantity),
+            }
+        }
+
+        tx expired(quantity: Int) {
+            input source {
+                from: Sender,
+                min_amount: Ada(quantity),
+            }
+
+            output {
+                to: Sender,
+                amount: source - fees,
+            }
+
+            validity {
+                until_slot: 10,
+            }
+        }
+    "#;
+
+    async fn resolve(template: &str, quantity: i128) -> Result<crate::resolve::TxEval, Error> {
+        let address = Address::from_bech32(ADDRESS).unwrap().to_vec();
+
+        let ledger = EmulatorLedger::default();
+        ledger.fund(&address, 500_000_000);
+        ledger.advance_slots(100);
+
+        let protocol = Protocol::from_string(PROTOCOL.to_string()).load().unwrap();
+
+        let tx = protocol
+            .new_tx(template)
+            .unwrap()
+            .with_arg("Sender", ArgValue::Address(address.clone()))
+            .with_arg("Receiver", ArgValue::Address(address))
+            .with_arg("quantity", ArgValue::Int(quantity))
+            .apply()
+            .unwrap();
+
+        let options = ResolveOptions {
+            validate: true,
+            ..Default::default()
+        };
+
+        resolve_tx_with_options(tx, ledger, &options).await
+    }
+
+    fn validation_error(result: Result<crate::resolve::TxEval, Error>) -> ValidationError {
+        match result {
+            Err(Error::InvalidTx(err)) => err,
+            x => panic!("expected a validation error, got {x:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_tx() {
+        resolve("transfer", 10_000_000).await.unwrap();
+    }
+
+    fn ledger_error(err: &ValidationError) -> &PostAlonzoError {
+        match err.error() {
+            LedgerError::PostAlonzo(x) => x,
+            x => panic!("expected a post-alonzo error, got {x:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_output_below_min_utxo() {
+        let err = validation_error(resolve("transfer", 1).await);
+
+        assert!(matches!(
+            ledger_error(&err),
+            PostAlonzoError::MinLovelaceUnreached
+        ));
+
+        assert_eq!(err.block(), TxBlock::Output(0));
+    }
+
+    #[tokio::test]
+    async fn rejects_unbalanced_tx() {
+        let err = validation_error(resolve("unbalanced", 10_000_000).await);
+
+        assert!(matches!(
+            ledger_error(&err),
+            PostAlonzoError::PreservationOfValue
+        ));
+
+        assert_eq!(err.block(), TxBlock::Tx);
+    }
+
+    #[tokio::test]
+    async fn rejects_tx_outside_validity_interval() {
+        let err = validation_error(resolve("expired", 10_000_000).await);
+
+        assert!(matches!(
+            ledger_error(&err),
+            PostAlonzoError::BlockExceedsValInt
+        ));
+
+        assert_eq!(err.block(), TxBlock::Validity);
+    }
+}
@@ -48,6 +48,20 @@ pub struct InvalidTargetTypeError {
     span: Span,
 }
 
+#[derive(Debug, thiserror::Error, miette::Diagnostic, PartialEq, Eq)]
+#[error("invalid threshold, {required} required out of {available} scripts")]
+#[diagnostic(code(tx3::invalid_threshold))]
+pub struct InvalidThresholdError {
+    pub required: i64,
+    pub available: usize,
+
+    #[source_code]
+    src: Option<String>,
+
+    #[label]
+    span: Span,
+}
+
 #[derive(thiserror::Error, Debug, miette::Diagnostic, PartialEq, Eq)]
 pub enum Error {
     #[error("duplicate definition: {0}")]
@@ -70,6 +84,10 @@ pub enum Error {
     #[error(transparent)]
     #[diagnostic(transparent)]
     InvalidTargetType(#[from] InvalidTargetTypeError),
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    InvalidThreshold(#[from] InvalidThresholdError),
 }
 
 impl Error {
@@ -78,6 +96,7 @@ impl Error {
             Self::NotInScope(x) => &x.span,
             Self::InvalidSymbol(x) => &x.span,
             Self::InvalidTargetType(x) => &x.span,
+            Self::InvalidThreshold(x) => &x.span,
             _ => &Span::DUMMY,
         }
     }
@@ -122,6 +141,15 @@ impl Error {
             span: ast.span().clone(),
         })
     }
+
+    pub fn invalid_threshold(required: i64, available: usize, span: &Span) -> Self {
+        Self::InvalidThreshold(InvalidThresholdError {
+            required,
+            available,
+            src: None,
+            span: span.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Default)]
@@ -381,6 +409,7 @@ impl Analyzable for PolicyField {
             PolicyField::Hash(x) => x.analyze(parent),
             PolicyField::Script(x) => x.analyze(parent),
             PolicyField::Ref(x) => x.analyze(parent),
+            PolicyField::Language(_) => AnalyzeReport::default(),
         }
     }
 
@@ -389,6 +418,7 @@ impl Analyzable for PolicyField {
             PolicyField::Hash(x) => x.is_resolved(),
             PolicyField::Script(x) => x.is_resolved(),
             PolicyField::Ref(x) => x.is_resolved(),
+            PolicyField::Language(_) => true,
         }
     }
 }
@@ -407,6 +437,7 @@ impl Analyzable for PolicyDef {
         match &mut self.value {
             PolicyValue::Constructor(x) => x.analyze(parent),
             PolicyValue::Assign(_) => AnalyzeReport::default(),
+            PolicyValue::Native(x) => x.analyze(parent),
         }
     }
 
@@ -414,6 +445,7 @@ impl Analyzable for PolicyDef {
         match &self.value {
             PolicyValue::Constructor(x) => x.is_resolved(),
             PolicyValue::Assign(_) => true,
+            PolicyValue::Native(x) => x.is_resolved(),
         }
     }
 }
@@ -740,6 +772,34 @@ impl Analyzable for InputBlock {
     }
 }
 
+impl Analyzable for CollateralBlockField {
+    fn analyze(&mut self, parent: Option<Rc<Scope>>) -> AnalyzeReport {
+        match self {
+            CollateralBlockField::From(x) => x.analyze(parent),
+            CollateralBlockField::MinAmount(x) => x.analyze(parent),
+            CollateralBlockField::Ref(x) => x.analyze(parent),
+        }
+    }
+
+    fn is_resolved(&self) -> bool {
+        match self {
+            CollateralBlockField::From(x) => x.is_resolved(),
+            CollateralBlockField::MinAmount(x) => x.is_resolved(),
+            CollateralBlockField::Ref(x) => x.is_resolved(),
+        }
+    }
+}
+
+impl Analyzable for CollateralBlock {
+    fn analyze(&mut self, parent: Option<Rc<Scope>>) -> AnalyzeReport {
+        self.fields.analyze(parent)
+    }
+
+    fn is_resolved(&self) -> bool {
+        self.fields.is_resolved()
+    }
+}
+
 impl Analyzable for MetadataBlockField {
     fn analyze(&mut self, parent: Option<Rc<Scope>>) -> AnalyzeReport {
         // TODO: check keys are actually numbers
@@ -789,6 +849,7 @@ impl Analyzable for OutputBlockField {
             OutputBlockField::To(x) => x.analyze(parent),
             OutputBlockField::Amount(x) => x.analyze(parent),
             OutputBlockField::Datum(x) => x.analyze(parent),
+            OutputBlockField::DatumHash(x) => x.analyze(parent),
         }
     }
 
@@ -797,6 +858,7 @@ impl Analyzable for OutputBlockField {
             OutputBlockField::To(x) => x.is_resolved(),
             OutputBlockField::Amount(x) => x.is_resolved(),
             OutputBlockField::Datum(x) => x.is_resolved(),
+            OutputBlockField::DatumHash(x) => x.is_resolved(),
         }
     }
 }
@@ -947,7 +1009,18 @@ impl Analyzable for TxDef {
 
         let signers = self.signers.analyze(self.scope.clone());
 
-        params + input_types + inputs + outputs + mints + adhoc + validity + metadata + signers
+        let collateral = self.collateral.analyze(self.scope.clone());
+
+        params
+            + input_types
+            + inputs
+            + outputs
+            + mints
+            + adhoc
+            + validity
+            + metadata
+            + signers
+            + collateral
     }
 
     fn is_resolved(&self) -> bool {
@@ -955,6 +1028,7 @@ impl Analyzable for TxDef {
             && self.outputs.is_resolved()
             && self.mints.is_resolved()
             && self.adhoc.is_resolved()
+            && self.collateral.is_resolved()
     }
 }
 
@@ -969,16 +1043,32 @@ fn ada_asset_def() -> AssetDef {
 
 impl Analyzable for Program {
     fn analyze(&mut self, parent: Option<Rc<Scope>>) -> AnalyzeReport {
-        let mut scope = Scope::new(parent);
+        let mut parties = Scope::new(parent);
 
         for party in self.parties.iter() {
-            scope.track_party_def(party);
+            parties.track_party_def(party);
         }
 
+        let parties = Rc::new(parties);
+
+        // policies need to be analyzed before being tracked so that the symbol
+        // keeps any party referenced by the definition (eg: native scripts)
+        let policies = self.policies.analyze(Some(parties.clone()));
+
+        let mut defs = Scope::new(Some(parties));
+
         for policy in self.policies.iter() {
-            scope.track_policy_def(policy);
+            defs.track_policy_def(policy);
         }
 
+        let defs = Rc::new(defs);
+
+        // assets need to be analyzed before being tracked so that the symbol
+        // keeps any policy referenced by the definition
+        let assets = self.assets.analyze(Some(defs.clone()));
+
+        let mut scope = Scope::new(Some(defs));
+
         scope.track_asset_def(&ada_asset_def());
 
         for asset in self.assets.iter() {
@@ -991,13 +1081,6 @@ impl Analyzable for Program {
 
         self.scope = Some(Rc::new(scope));
 
-        // TODO: Add parties
-        // let parties = self.parties.analyze(self.scope.clone());
-
-        let policies = self.policies.analyze(self.scope.clone());
-
-        let assets = self.assets.analyze(self.scope.clone());
-
         let types = self.types.analyze(self.scope.clone());
 
         let txs = self.txs.analyze(self.scope.clone());
@@ -1073,4 +1156,24 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn rejects_unsatisfiable_thresholds() {
+        let mut ast = crate::parsing::parse_string(
+            "party Alice; native_policy P = at_least(2) { sig(Alice) };",
+        )
+        .unwrap();
+
+        let report = analyze(&mut ast);
+
+        assert_eq!(
+            report.errors,
+            vec![Error::InvalidThreshold(InvalidThresholdError {
+                required: 2,
+                available: 1,
+                src: None,
+                span: Span::DUMMY,
+            })]
+        );
+    }
 }
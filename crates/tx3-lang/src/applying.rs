@@ -26,6 +26,14 @@ fn arg_value_into_expr(arg: ArgValue) -> ir::Expression {
         ArgValue::Bytes(x) => ir::Expression::Bytes(x),
         ArgValue::UtxoSet(x) => ir::Expression::UtxoSet(x),
         ArgValue::UtxoRef(x) => ir::Expression::UtxoRefs(vec![x]),
+        ArgValue::List(x) => ir::Expression::List(x.into_iter().map(arg_value_into_expr).collect()),
+        ArgValue::Struct {
+            constructor,
+            fields,
+        } => ir::Expression::Struct(ir::StructExpr {
+            constructor,
+            fields: fields.into_iter().map(arg_value_into_expr).collect(),
+        }),
     }
 }
 
@@ -192,7 +200,7 @@ where
     }
 }
 
-impl<T> Apply for HashMap<String, T>
+impl<T> Apply for BTreeMap<String, T>
 where
     T: Apply,
 {
@@ -377,7 +385,12 @@ impl Apply for ir::PolicyExpr {
             defined.map(TryFrom::try_from).transpose()?
         };
 
-        Ok(Self { name, hash, script })
+        Ok(Self {
+            name,
+            hash,
+            script,
+            language: self.language,
+        })
     }
 
     fn apply_inputs(self, args: &BTreeMap<String, HashSet<Utxo>>) -> Result<Self, Error> {
@@ -399,7 +412,12 @@ impl Apply for ir::PolicyExpr {
             })
             .transpose()?;
 
-        Ok(Self { name, hash, script })
+        Ok(Self {
+            name,
+            hash,
+            script,
+            language: self.language,
+        })
     }
 
     fn apply_fees(self, fees: u64) -> Result<Self, Error> {
@@ -407,6 +425,7 @@ impl Apply for ir::PolicyExpr {
             name: self.name,
             hash: self.hash.apply_fees(fees)?,
             script: self.script.apply_fees(fees)?,
+            language: self.language,
         })
     }
 
@@ -456,6 +475,7 @@ impl Apply for ir::PolicyExpr {
             name: self.name,
             hash: self.hash.reduce_nested()?,
             script: self.script.reduce_nested()?,
+            language: self.language,
         })
     }
 }
@@ -911,6 +931,9 @@ impl Apply for ir::Expression {
             ir::Expression::List(x) => Ok(ir::Expression::List(x.apply_args(args)?)),
             ir::Expression::Assets(x) => Ok(ir::Expression::Assets(x.apply_args(args)?)),
             ir::Expression::EvalCustom(x) => Ok(ir::Expression::EvalCustom(x.apply_args(args)?)),
+            ir::Expression::AdHocDirective(x) => {
+                Ok(ir::Expression::AdHocDirective(x.apply_args(args)?))
+            }
             ir::Expression::EvalParameter(name, ty) => {
                 let defined = args.get(&name).cloned();
 
@@ -959,6 +982,9 @@ impl Apply for ir::Expression {
             ir::Expression::List(x) => Ok(ir::Expression::List(x.apply_inputs(args)?)),
             ir::Expression::Assets(x) => Ok(ir::Expression::Assets(x.apply_inputs(args)?)),
             ir::Expression::EvalCustom(x) => Ok(ir::Expression::EvalCustom(x.apply_inputs(args)?)),
+            ir::Expression::AdHocDirective(x) => {
+                Ok(ir::Expression::AdHocDirective(x.apply_inputs(args)?))
+            }
             _ => Ok(self),
         }
     }
@@ -974,6 +1000,9 @@ impl Apply for ir::Expression {
             ir::Expression::List(x) => Ok(ir::Expression::List(x.apply_fees(fees)?)),
             ir::Expression::Assets(x) => Ok(ir::Expression::Assets(x.apply_fees(fees)?)),
             ir::Expression::EvalCustom(x) => Ok(ir::Expression::EvalCustom(x.apply_fees(fees)?)),
+            ir::Expression::AdHocDirective(x) => {
+                Ok(ir::Expression::AdHocDirective(x.apply_fees(fees)?))
+            }
             _ => Ok(self),
         }
     }
@@ -1011,6 +1040,8 @@ impl Apply for ir::Expression {
             ir::Expression::Struct(x) => x.params(),
             ir::Expression::Assets(x) => x.params(),
             ir::Expression::EvalCustom(x) => x.params(),
+            ir::Expression::List(x) => x.params(),
+            ir::Expression::AdHocDirective(x) => x.params(),
             ir::Expression::EvalParameter(x, ty) => BTreeMap::from([(x.to_string(), ty.clone())]),
 
             // the remaining cases are constants, so we can just return them
@@ -1060,8 +1091,10 @@ impl Apply for ir::Expression {
     fn reduce_nested(self) -> Result<Self, Error> {
         match self {
             ir::Expression::Struct(x) => Ok(ir::Expression::Struct(x.reduce()?)),
+            ir::Expression::List(x) => Ok(ir::Expression::List(x.reduce()?)),
             ir::Expression::Assets(x) => Ok(ir::Expression::Assets(x.reduce()?)),
             ir::Expression::EvalCustom(x) => Ok(ir::Expression::EvalCustom(x.reduce()?)),
+            ir::Expression::AdHocDirective(x) => Ok(ir::Expression::AdHocDirective(x.reduce()?)),
             _ => Ok(self),
         }
     }
@@ -1072,6 +1105,7 @@ impl Apply for ir::Output {
         Ok(Self {
             address: self.address.apply_args(args)?,
             datum: self.datum.apply_args(args)?,
+            datum_hash: self.datum_hash.apply_args(args)?,
             amount: self.amount.apply_args(args)?,
         })
     }
@@ -1080,6 +1114,7 @@ impl Apply for ir::Output {
         Ok(Self {
             address: self.address.apply_inputs(args)?,
             datum: self.datum.apply_inputs(args)?,
+            datum_hash: self.datum_hash.apply_inputs(args)?,
             amount: self.amount.apply_inputs(args)?,
         })
     }
@@ -1088,18 +1123,23 @@ impl Apply for ir::Output {
         Ok(Self {
             address: self.address.apply_fees(fees)?,
             datum: self.datum.apply_fees(fees)?,
+            datum_hash: self.datum_hash.apply_fees(fees)?,
             amount: self.amount.apply_fees(fees)?,
         })
     }
 
     fn is_constant(&self) -> bool {
-        self.address.is_constant() && self.datum.is_constant() && self.amount.is_constant()
+        self.address.is_constant()
+            && self.datum.is_constant()
+            && self.datum_hash.is_constant()
+            && self.amount.is_constant()
     }
 
     fn params(&self) -> BTreeMap<String, ir::Type> {
         let mut params = BTreeMap::new();
         params.extend(self.address.params());
         params.extend(self.datum.params());
+        params.extend(self.datum_hash.params());
         params.extend(self.amount.params());
         params
     }
@@ -1117,6 +1157,7 @@ impl Apply for ir::Output {
         Ok(Self {
             address: self.address.reduce()?,
             datum: self.datum.reduce()?,
+            datum_hash: Apply::reduce(self.datum_hash)?,
             amount: self.amount.reduce()?,
         })
     }
@@ -1127,6 +1168,7 @@ impl Apply for ir::Mint {
         Ok(Self {
             amount: self.amount.apply_args(args)?,
             redeemer: self.redeemer.apply_args(args)?,
+            policy: self.policy.apply_args(args)?,
         })
     }
 
@@ -1134,6 +1176,7 @@ impl Apply for ir::Mint {
         Ok(Self {
             amount: self.amount.apply_inputs(args)?,
             redeemer: self.redeemer.apply_inputs(args)?,
+            policy: self.policy.apply_inputs(args)?,
         })
     }
 
@@ -1141,23 +1184,25 @@ impl Apply for ir::Mint {
         Ok(Self {
             amount: self.amount.apply_fees(fees)?,
             redeemer: self.redeemer.apply_fees(fees)?,
+            policy: self.policy.apply_fees(fees)?,
         })
     }
 
     fn is_constant(&self) -> bool {
-        self.amount.is_constant() && self.redeemer.is_constant()
+        self.amount.is_constant() && self.redeemer.is_constant() && self.policy.is_constant()
     }
 
     fn params(&self) -> BTreeMap<String, ir::Type> {
         let mut params = BTreeMap::new();
         params.extend(self.amount.params());
         params.extend(self.redeemer.params());
+        params.extend(self.policy.params());
         params
     }
 
     fn queries(&self) -> BTreeMap<String, ir::InputQuery> {
-        // mints don't have queries
-        BTreeMap::new()
+        // only the policy script might need to be resolved
+        self.policy.queries()
     }
 
     fn reduce_self(self) -> Result<Self, Error> {
@@ -1168,6 +1213,7 @@ impl Apply for ir::Mint {
         Ok(Self {
             amount: self.amount.reduce()?,
             redeemer: self.redeemer.reduce()?,
+            policy: Apply::reduce(self.policy)?,
         })
     }
 }
@@ -112,6 +112,7 @@ impl Symbol {
             Symbol::ParamVar(_, ty) => Some(ty.as_ref().clone()),
             Symbol::RecordField(x) => Some(x.r#type.clone()),
             Symbol::Input(_, ty) => Some(ty.as_ref().clone()),
+            Symbol::PolicyDef(_) => Some(Type::Bytes),
             x => {
                 dbg!(x);
                 None
@@ -371,6 +372,7 @@ pub enum OutputBlockField {
     To(Box<AddressExpr>),
     Amount(Box<AssetExpr>),
     Datum(Box<DataExpr>),
+    DatumHash(Box<DataExpr>),
 }
 
 impl OutputBlockField {
@@ -379,6 +381,7 @@ impl OutputBlockField {
             OutputBlockField::To(_) => "to",
             OutputBlockField::Amount(_) => "amount",
             OutputBlockField::Datum(_) => "datum",
+            OutputBlockField::DatumHash(_) => "datum_hash",
         }
     }
 }
@@ -503,6 +506,21 @@ pub enum PolicyField {
     Hash(DataExpr),
     Script(DataExpr),
     Ref(DataExpr),
+    Language(ScriptLanguageLiteral),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ScriptLanguage {
+    Native,
+    PlutusV1,
+    PlutusV2,
+    PlutusV3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScriptLanguageLiteral {
+    pub value: ScriptLanguage,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -517,6 +535,7 @@ impl PolicyConstructor {
             PolicyField::Hash(_) => field == "hash",
             PolicyField::Script(_) => field == "script",
             PolicyField::Ref(_) => field == "ref",
+            PolicyField::Language(_) => field == "language",
         })
     }
 }
@@ -525,6 +544,7 @@ impl PolicyConstructor {
 pub enum PolicyValue {
     Constructor(PolicyConstructor),
     Assign(HexStringLiteral),
+    Native(Box<crate::cardano::NativeScript>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -1,10 +1,10 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::BTreeMap, rc::Rc};
 
 use pest::iterators::Pair;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    analyzing::{Analyzable, AnalyzeReport},
+    analyzing::{self, Analyzable, AnalyzeReport},
     ast::{DataExpr, Scope, Span},
     ir,
     lowering::IntoLower,
@@ -56,7 +56,7 @@ impl IntoLower for VoteDelegationCertificate {
     fn into_lower(&self) -> Result<Self::Output, crate::lowering::Error> {
         Ok(ir::AdHocDirective {
             name: "vote_delegation_certificate".to_string(),
-            data: HashMap::from([
+            data: BTreeMap::from([
                 ("drep".to_string(), self.drep.into_lower()?),
                 ("stake".to_string(), self.stake.into_lower()?),
             ]),
@@ -154,3 +154,195 @@ impl IntoLower for CardanoBlock {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NativeScriptGroup {
+    pub scripts: Vec<NativeScript>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NativeScriptAtLeast {
+    pub required: i64,
+    pub scripts: Vec<NativeScript>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NativeScriptCondition {
+    pub value: DataExpr,
+    pub span: Span,
+}
+
+/// A Cardano native script (multisig / timelock), as used by `native_policy`
+/// definitions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NativeScript {
+    All(NativeScriptGroup),
+    Any(NativeScriptGroup),
+    AtLeast(NativeScriptAtLeast),
+    Sig(NativeScriptCondition),
+    After(NativeScriptCondition),
+    Before(NativeScriptCondition),
+}
+
+impl NativeScript {
+    fn parse_scripts(pairs: pest::iterators::Pairs<Rule>) -> Result<Vec<NativeScript>, Error> {
+        pairs.map(NativeScript::parse).collect()
+    }
+}
+
+impl AstNode for NativeScript {
+    const RULE: Rule = Rule::native_script;
+
+    fn parse(pair: Pair<Rule>) -> Result<Self, Error> {
+        let span = pair.as_span().into();
+
+        match pair.as_rule() {
+            Rule::native_script_all => Ok(NativeScript::All(NativeScriptGroup {
+                scripts: Self::parse_scripts(pair.into_inner())?,
+                span,
+            })),
+            Rule::native_script_any => Ok(NativeScript::Any(NativeScriptGroup {
+                scripts: Self::parse_scripts(pair.into_inner())?,
+                span,
+            })),
+            Rule::native_script_at_least => {
+                let mut inner = pair.into_inner();
+                let required = inner.next().unwrap();
+
+                let required = required.as_str().parse().map_err(|_| {
+                    pest::error::Error::new_from_span(
+                        pest::error::ErrorVariant::<Rule>::CustomError {
+                            message: format!("invalid threshold {}", required.as_str()),
+                        },
+                        required.as_span(),
+                    )
+                })?;
+
+                Ok(NativeScript::AtLeast(NativeScriptAtLeast {
+                    required,
+                    scripts: Self::parse_scripts(inner)?,
+                    span,
+                }))
+            }
+            Rule::native_script_sig => Ok(NativeScript::Sig(NativeScriptCondition {
+                value: DataExpr::parse(pair.into_inner().next().unwrap())?,
+                span,
+            })),
+            Rule::native_script_after => Ok(NativeScript::After(NativeScriptCondition {
+                value: DataExpr::parse(pair.into_inner().next().unwrap())?,
+                span,
+            })),
+            Rule::native_script_before => Ok(NativeScript::Before(NativeScriptCondition {
+                value: DataExpr::parse(pair.into_inner().next().unwrap())?,
+                span,
+            })),
+            x => unreachable!("Unexpected rule in native_script: {:?}", x),
+        }
+    }
+
+    fn span(&self) -> &Span {
+        match self {
+            NativeScript::All(x) => &x.span,
+            NativeScript::Any(x) => &x.span,
+            NativeScript::AtLeast(x) => &x.span,
+            NativeScript::Sig(x) => &x.span,
+            NativeScript::After(x) => &x.span,
+            NativeScript::Before(x) => &x.span,
+        }
+    }
+}
+
+impl Analyzable for NativeScript {
+    fn analyze(&mut self, parent: Option<Rc<Scope>>) -> AnalyzeReport {
+        match self {
+            NativeScript::All(x) | NativeScript::Any(x) => x.scripts.analyze(parent),
+            NativeScript::AtLeast(x) => {
+                let report = x.scripts.analyze(parent);
+
+                // a threshold above the number of scripts can never be met
+                let available = x.scripts.len();
+
+                match usize::try_from(x.required) {
+                    Ok(required) if required <= available => report,
+                    _ => {
+                        let error =
+                            analyzing::Error::invalid_threshold(x.required, available, &x.span);
+                        report + error.into()
+                    }
+                }
+            }
+            NativeScript::Sig(x) | NativeScript::After(x) | NativeScript::Before(x) => {
+                x.value.analyze(parent)
+            }
+        }
+    }
+
+    fn is_resolved(&self) -> bool {
+        match self {
+            NativeScript::All(x) | NativeScript::Any(x) => x.scripts.is_resolved(),
+            NativeScript::AtLeast(x) => x.scripts.is_resolved(),
+            NativeScript::Sig(x) | NativeScript::After(x) | NativeScript::Before(x) => {
+                x.value.is_resolved()
+            }
+        }
+    }
+}
+
+fn lower_native_scripts(
+    scripts: &[NativeScript],
+) -> Result<ir::Expression, crate::lowering::Error> {
+    let scripts = scripts
+        .iter()
+        .map(|x| x.into_lower())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ir::Expression::List(scripts))
+}
+
+impl IntoLower for NativeScript {
+    type Output = ir::Expression;
+
+    fn into_lower(&self) -> Result<Self::Output, crate::lowering::Error> {
+        let (name, data) = match self {
+            NativeScript::All(x) => (
+                "native_script_all",
+                BTreeMap::from([("scripts".to_string(), lower_native_scripts(&x.scripts)?)]),
+            ),
+            NativeScript::Any(x) => (
+                "native_script_any",
+                BTreeMap::from([("scripts".to_string(), lower_native_scripts(&x.scripts)?)]),
+            ),
+            NativeScript::AtLeast(x) => (
+                "native_script_at_least",
+                BTreeMap::from([
+                    (
+                        "required".to_string(),
+                        ir::Expression::Number(x.required as i128),
+                    ),
+                    ("scripts".to_string(), lower_native_scripts(&x.scripts)?),
+                ]),
+            ),
+            NativeScript::Sig(x) => (
+                "native_script_sig",
+                BTreeMap::from([("key".to_string(), x.value.into_lower()?)]),
+            ),
+            NativeScript::After(x) => (
+                "native_script_after",
+                BTreeMap::from([("slot".to_string(), x.value.into_lower()?)]),
+            ),
+            NativeScript::Before(x) => (
+                "native_script_before",
+                BTreeMap::from([("slot".to_string(), x.value.into_lower()?)]),
+            ),
+        };
+
+        Ok(ir::Expression::AdHocDirective(Box::new(
+            ir::AdHocDirective {
+                name: name.to_string(),
+                data,
+            },
+        )))
+    }
+}
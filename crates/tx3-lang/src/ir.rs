@@ -7,15 +7,89 @@
 //! This module is not intended to be used directly by end-users. See
 //! [`lower`](crate::lower) for lowering an AST to the intermediate
 //! representation.
+//!
+//! # JSON encoding
+//!
+//! Besides the compact bincode encoding of [`ProtoTx::ir_bytes`], the IR has
+//! a JSON encoding meant to be read by humans and produced by non-Rust
+//! tooling. It's the serde representation of [`Tx`]:
+//!
+//! - structs are objects keyed by field name. Optional fields may be omitted
+//!   or set to `null`.
+//! - enums are externally tagged: unit variants are plain strings (e.g.
+//!   `"FeeQuery"`) and the rest are single-key objects (e.g.
+//!   `{ "Number": 10 }` or `{ "EvalParameter": ["sender", "Address"] }`).
+//! - bytes, addresses, hashes and tx ids are hex strings. Arrays of numbers
+//!   are also accepted when parsing.
+//!
+//! The IR version isn't part of the encoding, it travels along with it the
+//! same way it does for the bincode encoding. See
+//! [`ProtoTx::to_ir_json`] and [`ProtoTx::from_ir_json`].
+//!
+//! [`ProtoTx::ir_bytes`]: crate::ProtoTx::ir_bytes
+//! [`ProtoTx::to_ir_json`]: crate::ProtoTx::to_ir_json
+//! [`ProtoTx::from_ir_json`]: crate::ProtoTx::from_ir_json
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
 use crate::{Utxo, UtxoRef};
 
-pub const IR_VERSION: &str = "v1alpha5";
+pub const IR_VERSION: &str = "v1alpha6";
+
+/// Serde representation of bytes as hex strings, also accepting arrays of
+/// numbers when deserializing.
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Hex(String),
+        Raw(Vec<u8>),
+    }
+
+    impl Repr {
+        fn into_bytes<E: serde::de::Error>(self) -> Result<Vec<u8>, E> {
+            match self {
+                Repr::Hex(x) => hex::decode(x).map_err(E::custom),
+                Repr::Raw(x) => Ok(x),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Repr::deserialize(deserializer)?.into_bytes()
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            bytes: &Option<Vec<u8>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match bytes {
+                Some(x) => super::serialize(x, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec<u8>>, D::Error> {
+            Option::<Repr>::deserialize(deserializer)?
+                .map(Repr::into_bytes)
+                .transpose()
+        }
+    }
+}
 
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct StructExpr {
@@ -61,7 +135,7 @@ pub struct AssetExpr {
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct AdHocDirective {
     pub name: String,
-    pub data: HashMap<String, Expression>,
+    pub data: BTreeMap<String, Expression>,
 }
 
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -83,11 +157,22 @@ impl ScriptSource {
     }
 }
 
+#[derive(
+    Encode, Decode, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum ScriptLanguage {
+    Native,
+    PlutusV1,
+    PlutusV2,
+    PlutusV3,
+}
+
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct PolicyExpr {
     pub name: String,
     pub hash: Expression,
     pub script: Option<ScriptSource>,
+    pub language: Option<ScriptLanguage>,
 }
 
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -116,12 +201,12 @@ pub enum Expression {
     List(Vec<Expression>),
     Tuple(Box<(Expression, Expression)>),
     Struct(StructExpr),
-    Bytes(Vec<u8>),
+    Bytes(#[serde(with = "hex_bytes")] Vec<u8>),
     Number(i128),
     Bool(bool),
     String(String),
-    Address(Vec<u8>),
-    Hash(Vec<u8>),
+    Address(#[serde(with = "hex_bytes")] Vec<u8>),
+    Hash(#[serde(with = "hex_bytes")] Vec<u8>),
     UtxoRefs(Vec<UtxoRef>),
     UtxoSet(HashSet<Utxo>),
     Assets(Vec<AssetExpr>),
@@ -156,7 +241,7 @@ pub struct InputQuery {
 pub struct Input {
     pub name: String,
     pub query: Option<InputQuery>,
-    pub refs: HashSet<UtxoRef>,
+    pub refs: BTreeSet<UtxoRef>,
     pub redeemer: Option<Expression>,
     pub policy: Option<PolicyExpr>,
 }
@@ -165,6 +250,7 @@ pub struct Input {
 pub struct Output {
     pub address: Option<Expression>,
     pub datum: Option<Expression>,
+    pub datum_hash: Option<Expression>,
     pub amount: Option<Expression>,
 }
 
@@ -178,6 +264,7 @@ pub struct Validity {
 pub struct Mint {
     pub amount: Option<Expression>,
     pub redeemer: Option<Expression>,
+    pub policy: Option<PolicyExpr>,
 }
 
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
@@ -30,6 +30,7 @@ pub mod ast;
 pub mod ir;
 pub mod loading;
 pub mod lowering;
+pub mod migrating;
 pub mod parsing;
 
 // chain specific
@@ -42,8 +43,11 @@ macro_rules! include_tx3_build {
     };
 }
 
-#[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(
+    Encode, Decode, Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
 pub struct UtxoRef {
+    #[serde(with = "ir::hex_bytes")]
     pub txid: Vec<u8>,
     pub index: u32,
 }
@@ -51,8 +55,11 @@ pub struct UtxoRef {
 #[derive(Encode, Decode, Serialize, Deserialize, Debug, Clone)]
 pub struct Utxo {
     pub r#ref: UtxoRef,
+    #[serde(with = "ir::hex_bytes")]
     pub address: Vec<u8>,
     pub datum: Option<ir::Expression>,
+    #[serde(default, with = "ir::hex_bytes::option")]
+    pub datum_hash: Option<Vec<u8>>,
     pub assets: Vec<ir::AssetExpr>,
     pub script: Option<ir::Expression>,
 }
@@ -73,15 +80,20 @@ impl Eq for Utxo {}
 
 pub type UtxoSet = HashSet<Utxo>;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Encode, Decode)]
 pub enum ArgValue {
     Int(i128),
     Bool(bool),
     String(String),
-    Bytes(Vec<u8>),
-    Address(Vec<u8>),
+    Bytes(#[serde(with = "ir::hex_bytes")] Vec<u8>),
+    Address(#[serde(with = "ir::hex_bytes")] Vec<u8>),
     UtxoSet(UtxoSet),
     UtxoRef(UtxoRef),
+    List(Vec<ArgValue>),
+    Struct {
+        constructor: usize,
+        fields: Vec<ArgValue>,
+    },
 }
 
 impl From<Vec<u8>> for ArgValue {
@@ -167,7 +179,7 @@ pub use applying::{apply_args, apply_fees, apply_inputs, find_params, find_queri
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Encode, Decode)]
 pub struct ProtoTx {
     ir: ir::Tx,
     args: std::collections::BTreeMap<String, ArgValue>,
@@ -228,6 +240,32 @@ impl ProtoTx {
         Ok(tx.into())
     }
 
+    /// Encodes the tx along with the args, inputs and fees set on it that
+    /// weren't applied yet, so that it can be finished somewhere else. Unlike
+    /// [`ir_bytes`](Self::ir_bytes), the encoding carries its IR version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let config = bincode::config::standard();
+        bincode::encode_to_vec((ir::IR_VERSION, self), config).unwrap()
+    }
+
+    /// Decodes a tx encoded with [`to_bytes`](Self::to_bytes), keeping its
+    /// pending args, inputs and fees.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, migrating::Error> {
+        let config = bincode::config::standard();
+        let (version, read) = bincode::decode_from_slice::<String, _>(bytes, config)?;
+
+        if version != ir::IR_VERSION {
+            return Err(migrating::Error::UnsupportedVersion {
+                found: version,
+                supported: &[ir::IR_VERSION],
+            });
+        }
+
+        let (tx, _) = bincode::decode_from_slice(&bytes[read..], config)?;
+
+        Ok(tx)
+    }
+
     pub fn ir_bytes(&self) -> Vec<u8> {
         let config = bincode::config::standard();
         bincode::encode_to_vec(&self.ir, config).unwrap()
@@ -238,6 +276,26 @@ impl ProtoTx {
         let (ir, _) = bincode::decode_from_slice::<ir::Tx, _>(bytes, config)?;
         Ok(Self::from(ir))
     }
+
+    /// Decodes IR bytes encoded with the given IR version, migrating them
+    /// into the current one when they come from a previous version.
+    pub fn from_versioned_ir_bytes(version: &str, bytes: &[u8]) -> Result<Self, migrating::Error> {
+        let ir = migrating::decode_ir(version, bytes)?;
+        Ok(Self::from(ir))
+    }
+
+    /// Encodes the IR of the tx as pretty-printed JSON. See the
+    /// [`ir`](crate::ir#json-encoding) module for details of the encoding.
+    pub fn to_ir_json(&self) -> String {
+        serde_json::to_string_pretty(&self.ir).expect("IR is always JSON-serializable")
+    }
+
+    /// Decodes the JSON encoding of the IR of a tx, written with the given
+    /// IR version.
+    pub fn from_ir_json(version: &str, json: &str) -> Result<Self, migrating::Error> {
+        let ir = migrating::decode_ir_json(version, json)?;
+        Ok(Self::from(ir))
+    }
 }
 
 impl AsRef<ir::Tx> for ProtoTx {
@@ -285,6 +343,7 @@ mod tests {
                 },
                 address: b"abababa".to_vec(),
                 datum: None,
+                datum_hash: None,
                 assets: vec![ir::AssetExpr {
                     policy: ir::Expression::Bytes(b"abababa".to_vec()),
                     asset_name: ir::Expression::Bytes(b"asset".to_vec()),
@@ -299,4 +358,55 @@ mod tests {
         dbg!(&tx.find_params());
         dbg!(&tx.find_queries());
     }
+
+    #[test]
+    fn ir_json_roundtrip() {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let code = format!("{manifest_dir}/../../examples/faucet.tx3");
+
+        let protocol = Protocol::from_file(&code).load().unwrap();
+        let tx = protocol.new_tx("claim_with_password").unwrap();
+
+        let json = tx.to_ir_json();
+
+        // bytes are encoded as hex strings
+        assert!(!json.contains("\"Bytes\": ["));
+
+        let decoded = ProtoTx::from_ir_json(ir::IR_VERSION, &json).unwrap();
+        assert_eq!(decoded.ir_bytes(), tx.ir_bytes());
+    }
+
+    #[test]
+    fn partial_application_roundtrip() {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let code = format!("{manifest_dir}/../../examples/transfer.tx3");
+
+        let protocol = Protocol::from_file(&code).load().unwrap();
+
+        // the backend fills what it knows, some of it already applied
+        let tx = protocol
+            .new_tx("transfer")
+            .unwrap()
+            .with_arg("sender", ArgValue::Address(b"sender".to_vec()))
+            .apply()
+            .unwrap()
+            .with_arg("quantity", ArgValue::Int(100));
+
+        let mut tx = ProtoTx::from_bytes(&tx.to_bytes()).unwrap();
+
+        assert!(matches!(tx.args.get("quantity"), Some(ArgValue::Int(100))));
+
+        // the wallet finishes the rest
+        tx.set_arg("receiver", ArgValue::Address(b"receiver".to_vec()));
+
+        let tx = tx.apply().unwrap();
+        assert!(tx.find_params().is_empty());
+
+        let bytes = bincode::encode_to_vec(("v0alpha0", &tx), bincode::config::standard()).unwrap();
+
+        assert!(matches!(
+            ProtoTx::from_bytes(&bytes),
+            Err(migrating::Error::UnsupportedVersion { .. })
+        ));
+    }
 }
@@ -3,7 +3,7 @@
 //! This module takes an AST and performs lowering on it. It converts the AST
 //! into the intermediate representation (IR) of the Tx3 language.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Deref;
 
 use crate::ast;
@@ -137,6 +137,7 @@ impl IntoLower for ast::Identifier {
                 ir::Type::Address,
             )),
             ast::Symbol::Input(n, _) => Ok(ir::Expression::EvalInputDatum(n.clone())),
+            ast::Symbol::PolicyDef(x) => Ok(x.into_lower()?.hash),
             _ => {
                 dbg!(&self);
                 todo!();
@@ -206,6 +207,22 @@ impl IntoLower for ast::PolicyField {
             ast::PolicyField::Hash(x) => x.into_lower(),
             ast::PolicyField::Script(x) => x.into_lower(),
             ast::PolicyField::Ref(x) => x.into_lower(),
+            ast::PolicyField::Language(_) => Err(Error::InvalidAst(
+                "policy language is not an expression".to_string(),
+            )),
+        }
+    }
+}
+
+impl IntoLower for ast::ScriptLanguage {
+    type Output = ir::ScriptLanguage;
+
+    fn into_lower(&self) -> Result<Self::Output, Error> {
+        match self {
+            ast::ScriptLanguage::Native => Ok(ir::ScriptLanguage::Native),
+            ast::ScriptLanguage::PlutusV1 => Ok(ir::ScriptLanguage::PlutusV1),
+            ast::ScriptLanguage::PlutusV2 => Ok(ir::ScriptLanguage::PlutusV2),
+            ast::ScriptLanguage::PlutusV3 => Ok(ir::ScriptLanguage::PlutusV3),
         }
     }
 }
@@ -219,13 +236,38 @@ impl IntoLower for ast::PolicyDef {
                 name: self.name.clone(),
                 hash: ir::Expression::Hash(hex::decode(&x.value)?),
                 script: None,
+                language: None,
             }),
+            ast::PolicyValue::Native(x) => {
+                let script = x.into_lower()?;
+
+                // the hash depends on the (applied) script, so it's left for the
+                // chain-specific compiler to compute
+                let hash = ir::Expression::AdHocDirective(Box::new(ir::AdHocDirective {
+                    name: "native_script_hash".to_string(),
+                    data: BTreeMap::from([("script".to_string(), script.clone())]),
+                }));
+
+                Ok(ir::PolicyExpr {
+                    name: self.name.clone(),
+                    hash,
+                    script: Some(ir::ScriptSource::Embedded(script)),
+                    language: Some(ir::ScriptLanguage::Native),
+                })
+            }
             ast::PolicyValue::Constructor(x) => {
                 let hash = x
                     .find_field("hash")
                     .ok_or(Error::InvalidAst("Missing policy hash".to_string()))?
                     .into_lower()?;
 
+                // hash literals lower as bytes, but the policy is identified by
+                // its hash in the same way as the assign form
+                let hash = match hash {
+                    ir::Expression::Bytes(x) => ir::Expression::Hash(x),
+                    x => x,
+                };
+
                 let ref_field = x.find_field("ref");
                 let script_field = x.find_field("script");
 
@@ -242,10 +284,20 @@ impl IntoLower for ast::PolicyDef {
                     (None, None) => None,
                 };
 
+                let language = x
+                    .fields
+                    .iter()
+                    .find_map(|x| match x {
+                        ast::PolicyField::Language(x) => Some(x.value.into_lower()),
+                        _ => None,
+                    })
+                    .transpose()?;
+
                 Ok(ir::PolicyExpr {
                     name: self.name.clone(),
                     hash,
                     script,
+                    language,
                 })
             }
         }
@@ -463,7 +515,7 @@ impl IntoLower for ast::InputBlock {
                 r#ref: r#ref.into_lower()?,
             }
             .into(),
-            refs: HashSet::new(),
+            refs: BTreeSet::new(),
             redeemer: redeemer.into_lower()?,
             policy,
         };
@@ -480,6 +532,7 @@ impl IntoLower for ast::OutputBlockField {
             ast::OutputBlockField::To(x) => x.into_lower(),
             ast::OutputBlockField::Amount(x) => x.into_lower(),
             ast::OutputBlockField::Datum(x) => x.into_lower(),
+            ast::OutputBlockField::DatumHash(x) => x.into_lower(),
         }
     }
 }
@@ -491,6 +544,7 @@ impl IntoLower for ast::OutputBlock {
         Ok(ir::Output {
             address: self.find("to").into_lower()?,
             datum: self.find("datum").into_lower()?,
+            datum_hash: self.find("datum_hash").into_lower()?,
             amount: self.find("amount").into_lower()?,
         })
     }
@@ -512,8 +566,8 @@ impl IntoLower for ast::ValidityBlock {
 
     fn into_lower(&self) -> Result<Self::Output, Error> {
         Ok(ir::Validity {
-            since: self.find("valid_since").into_lower()?,
-            until: self.find("valid_until").into_lower()?,
+            since: self.find("since_slot").into_lower()?,
+            until: self.find("until_slot").into_lower()?,
         })
     }
 }
@@ -529,13 +583,48 @@ impl IntoLower for ast::MintBlockField {
     }
 }
 
+fn find_data_expr_policy(expr: &ast::DataExpr) -> Result<Option<ir::PolicyExpr>, Error> {
+    match expr {
+        ast::DataExpr::Identifier(x) => match x.try_symbol()? {
+            ast::Symbol::PolicyDef(x) => Ok(Some(x.into_lower()?)),
+            _ => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Finds the policy definition (if any) behind the assets of an expression.
+///
+/// Only the first policy found is returned since each mint block is expected
+/// to be governed by a single policy.
+fn find_asset_policy(expr: &ast::AssetExpr) -> Result<Option<ir::PolicyExpr>, Error> {
+    match expr {
+        ast::AssetExpr::StaticConstructor(x) => {
+            let asset_def = coerce_identifier_into_asset_def(&x.r#type)?;
+            find_data_expr_policy(&asset_def.policy)
+        }
+        ast::AssetExpr::AnyConstructor(x) => find_data_expr_policy(&x.policy),
+        ast::AssetExpr::BinaryOp(x) => match find_asset_policy(&x.left)? {
+            Some(policy) => Ok(Some(policy)),
+            None => find_asset_policy(&x.right),
+        },
+        _ => Ok(None),
+    }
+}
+
 impl IntoLower for ast::MintBlock {
     type Output = ir::Mint;
 
     fn into_lower(&self) -> Result<Self::Output, Error> {
+        let policy = match self.find("amount") {
+            Some(ast::MintBlockField::Amount(x)) => find_asset_policy(x)?,
+            _ => None,
+        };
+
         Ok(ir::Mint {
             amount: self.find("amount").into_lower()?,
             redeemer: self.find("redeemer").into_lower()?,
+            policy,
         })
     }
 }
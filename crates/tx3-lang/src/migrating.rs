@@ -0,0 +1,266 @@
+//! Decoding of IR encoded by previous versions of the language.
+//!
+//! Compiled clients embed the IR bytes of their txs along with the version of
+//! the IR they were built with. Each supported previous version keeps a copy
+//! of the IR structures that changed since then, so that its bytes can be
+//! decoded and migrated into the current IR.
+
+use crate::ir::{self, IR_VERSION};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IR version '{found}' is not supported, expected one of {supported:?}")]
+    UnsupportedVersion {
+        found: String,
+        supported: &'static [&'static str],
+    },
+
+    #[error("can't decode IR bytes: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+
+    #[error("can't decode IR json: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// IR versions that can be decoded, the current one first.
+pub const SUPPORTED_VERSIONS: &[&str] = &[IR_VERSION, "v1alpha5"];
+
+/// IR of `v1alpha5`, which lacked script languages, datum hashes and mint
+/// policies.
+mod v1alpha5 {
+    use std::collections::HashSet;
+
+    use bincode::{Decode, Encode};
+
+    use crate::{ir, UtxoRef};
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct PolicyExpr {
+        pub name: String,
+        pub hash: ir::Expression,
+        pub script: Option<ir::ScriptSource>,
+    }
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct Input {
+        pub name: String,
+        pub query: Option<ir::InputQuery>,
+        pub refs: HashSet<UtxoRef>,
+        pub redeemer: Option<ir::Expression>,
+        pub policy: Option<PolicyExpr>,
+    }
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct Output {
+        pub address: Option<ir::Expression>,
+        pub datum: Option<ir::Expression>,
+        pub amount: Option<ir::Expression>,
+    }
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct Mint {
+        pub amount: Option<ir::Expression>,
+        pub redeemer: Option<ir::Expression>,
+    }
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct Tx {
+        pub fees: ir::Expression,
+        pub references: Vec<ir::Expression>,
+        pub inputs: Vec<Input>,
+        pub outputs: Vec<Output>,
+        pub validity: Option<ir::Validity>,
+        pub mints: Vec<Mint>,
+        pub adhoc: Vec<ir::AdHocDirective>,
+        pub collateral: Vec<ir::Collateral>,
+        pub signers: Option<ir::Signers>,
+        pub metadata: Vec<ir::Metadata>,
+    }
+
+    impl From<PolicyExpr> for ir::PolicyExpr {
+        fn from(value: PolicyExpr) -> Self {
+            // the language is inferred from the script when compiling
+            Self {
+                name: value.name,
+                hash: value.hash,
+                script: value.script,
+                language: None,
+            }
+        }
+    }
+
+    impl From<Input> for ir::Input {
+        fn from(value: Input) -> Self {
+            Self {
+                name: value.name,
+                query: value.query,
+                refs: value.refs.into_iter().collect(),
+                redeemer: value.redeemer,
+                policy: value.policy.map(Into::into),
+            }
+        }
+    }
+
+    impl From<Output> for ir::Output {
+        fn from(value: Output) -> Self {
+            Self {
+                address: value.address,
+                datum: value.datum,
+                datum_hash: None,
+                amount: value.amount,
+            }
+        }
+    }
+
+    impl From<Mint> for ir::Mint {
+        fn from(value: Mint) -> Self {
+            Self {
+                amount: value.amount,
+                redeemer: value.redeemer,
+                policy: None,
+            }
+        }
+    }
+
+    impl From<Tx> for ir::Tx {
+        fn from(value: Tx) -> Self {
+            Self {
+                fees: value.fees,
+                references: value.references,
+                inputs: value.inputs.into_iter().map(Into::into).collect(),
+                outputs: value.outputs.into_iter().map(Into::into).collect(),
+                validity: value.validity,
+                mints: value.mints.into_iter().map(Into::into).collect(),
+                adhoc: value.adhoc,
+                collateral: value.collateral,
+                signers: value.signers,
+                metadata: value.metadata,
+            }
+        }
+    }
+}
+
+fn decode<T: bincode::Decode<()>>(bytes: &[u8]) -> Result<T, Error> {
+    let config = bincode::config::standard();
+    let (value, _) = bincode::decode_from_slice(bytes, config)?;
+    Ok(value)
+}
+
+/// Decodes IR bytes encoded with the given version of the IR, migrating them
+/// into the current one.
+pub fn decode_ir(version: &str, bytes: &[u8]) -> Result<ir::Tx, Error> {
+    match version {
+        IR_VERSION => decode(bytes),
+        "v1alpha5" => decode::<v1alpha5::Tx>(bytes).map(Into::into),
+        _ => Err(Error::UnsupportedVersion {
+            found: version.to_string(),
+            supported: SUPPORTED_VERSIONS,
+        }),
+    }
+}
+
+/// Decodes the JSON encoding of the IR written with the given version of the
+/// IR. Fields added since a previous version are optional, so the JSON of
+/// all supported versions decodes as the current IR.
+pub fn decode_ir_json(version: &str, json: &str) -> Result<ir::Tx, Error> {
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        return Err(Error::UnsupportedVersion {
+            found: version.to_string(),
+            supported: SUPPORTED_VERSIONS,
+        });
+    }
+
+    Ok(serde_json::from_str(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v1alpha5_tx() -> v1alpha5::Tx {
+        let policy = v1alpha5::PolicyExpr {
+            name: "token".to_string(),
+            hash: ir::Expression::Hash(vec![1; 28]),
+            script: None,
+        };
+
+        v1alpha5::Tx {
+            fees: ir::Expression::FeeQuery,
+            references: vec![],
+            inputs: vec![v1alpha5::Input {
+                name: "source".to_string(),
+                query: Some(ir::InputQuery::default()),
+                refs: Default::default(),
+                redeemer: None,
+                policy: Some(policy),
+            }],
+            outputs: vec![v1alpha5::Output {
+                address: Some(ir::Expression::Address(vec![0; 57])),
+                datum: None,
+                amount: Some(ir::Expression::Number(10)),
+            }],
+            validity: None,
+            mints: vec![v1alpha5::Mint {
+                amount: Some(ir::Expression::Number(1)),
+                redeemer: None,
+            }],
+            adhoc: vec![],
+            collateral: vec![],
+            signers: None,
+            metadata: vec![],
+        }
+    }
+
+    #[test]
+    fn migrates_v1alpha5() {
+        let bytes = bincode::encode_to_vec(v1alpha5_tx(), bincode::config::standard()).unwrap();
+
+        let tx = decode_ir("v1alpha5", &bytes).unwrap();
+
+        assert_eq!(tx.inputs[0].name, "source");
+        assert_eq!(tx.inputs[0].policy.as_ref().unwrap().name, "token");
+        assert_eq!(tx.inputs[0].policy.as_ref().unwrap().language, None);
+        assert!(tx.outputs[0].datum_hash.is_none());
+        assert!(matches!(
+            tx.outputs[0].amount,
+            Some(ir::Expression::Number(10))
+        ));
+        assert!(tx.mints[0].policy.is_none());
+
+        // the old layout doesn't decode as the current version
+        assert!(decode_ir(IR_VERSION, &bytes).is_err());
+    }
+
+    #[test]
+    fn migrates_v1alpha5_json() {
+        let json = r#"{
+            "fees": "FeeQuery",
+            "references": [],
+            "inputs": [],
+            "outputs": [{ "address": { "Address": "00ff" }, "datum": null, "amount": null }],
+            "validity": null,
+            "mints": [{ "amount": null, "redeemer": null }],
+            "adhoc": [],
+            "collateral": [],
+            "signers": null,
+            "metadata": []
+        }"#;
+
+        let tx = decode_ir_json("v1alpha5", json).unwrap();
+
+        assert!(
+            matches!(&tx.outputs[0].address, Some(ir::Expression::Address(x)) if x == &[0, 255])
+        );
+        assert!(tx.outputs[0].datum_hash.is_none());
+        assert!(tx.mints[0].policy.is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let err = decode_ir("v1alpha3", &[]).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedVersion { found, .. } if found == "v1alpha3"));
+
+        let err = decode_ir_json("v1alpha3", "{}").unwrap_err();
+        assert!(matches!(err, Error::UnsupportedVersion { .. }));
+    }
+}
@@ -471,6 +471,11 @@ impl AstNode for OutputBlockField {
                 let x = OutputBlockField::Datum(DataExpr::parse(pair)?.into());
                 Ok(x)
             }
+            Rule::output_block_datum_hash => {
+                let pair = pair.into_inner().next().unwrap();
+                let x = OutputBlockField::DatumHash(DataExpr::parse(pair)?.into());
+                Ok(x)
+            }
             x => unreachable!("Unexpected rule in output_block_field: {:?}", x),
         }
     }
@@ -480,6 +485,7 @@ impl AstNode for OutputBlockField {
             Self::To(x) => x.span(),
             Self::Amount(x) => x.span(),
             Self::Datum(x) => x.span(),
+            Self::DatumHash(x) => x.span(),
         }
     }
 }
@@ -675,6 +681,9 @@ impl AstNode for PolicyField {
             Rule::policy_def_ref => Ok(PolicyField::Ref(DataExpr::parse(
                 pair.into_inner().next().unwrap(),
             )?)),
+            Rule::policy_def_language => Ok(PolicyField::Language(ScriptLanguageLiteral::parse(
+                pair.into_inner().next().unwrap(),
+            )?)),
             x => unreachable!("Unexpected rule in policy_field: {:?}", x),
         }
     }
@@ -684,10 +693,34 @@ impl AstNode for PolicyField {
             Self::Hash(x) => x.span(),
             Self::Script(x) => x.span(),
             Self::Ref(x) => x.span(),
+            Self::Language(x) => x.span(),
         }
     }
 }
 
+impl AstNode for ScriptLanguageLiteral {
+    const RULE: Rule = Rule::script_language;
+
+    fn parse(pair: Pair<Rule>) -> Result<Self, Error> {
+        let value = match pair.as_str() {
+            "native" => ScriptLanguage::Native,
+            "plutus_v1" => ScriptLanguage::PlutusV1,
+            "plutus_v2" => ScriptLanguage::PlutusV2,
+            "plutus_v3" => ScriptLanguage::PlutusV3,
+            x => unreachable!("Unexpected script language: {:?}", x),
+        };
+
+        Ok(ScriptLanguageLiteral {
+            value,
+            span: pair.as_span().into(),
+        })
+    }
+
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl AstNode for PolicyConstructor {
     const RULE: Rule = Rule::policy_def_constructor;
 
@@ -718,6 +751,9 @@ impl AstNode for PolicyValue {
             Rule::policy_def_assign => Ok(PolicyValue::Assign(HexStringLiteral::parse(
                 pair.into_inner().next().unwrap(),
             )?)),
+            Rule::policy_def_native => Ok(PolicyValue::Native(Box::new(
+                crate::cardano::NativeScript::parse(pair.into_inner().next().unwrap())?,
+            ))),
             x => unreachable!("Unexpected rule in policy_value: {:?}", x),
         }
     }
@@ -726,6 +762,7 @@ impl AstNode for PolicyValue {
         match self {
             Self::Constructor(x) => x.span(),
             Self::Assign(x) => x.span(),
+            Self::Native(x) => x.span(),
         }
     }
 }
@@ -1406,6 +1443,14 @@ mod tests {
         let _ = parse_string("tx swap() {}").unwrap();
     }
 
+    #[test]
+    fn rejects_out_of_range_threshold() {
+        let err = parse_string("native_policy P = at_least(99999999999999999999) { sig(A) };")
+            .unwrap_err();
+
+        assert_eq!(err.message, "invalid threshold 99999999999999999999");
+    }
+
     macro_rules! input_to_ast_check {
         ($ast:ty, $name:expr, $input:expr, $expected:expr) => {
             paste::paste! {
@@ -1673,6 +1718,86 @@ mod tests {
         }
     );
 
+    input_to_ast_check!(
+        PolicyDef,
+        "policy_def_constructor_with_language",
+        "policy MyPolicy {
+            hash: 0x1234567890,
+            script: 0x1234567890,
+            language: plutus_v3,
+        };",
+        PolicyDef {
+            name: "MyPolicy".to_string(),
+            value: PolicyValue::Constructor(PolicyConstructor {
+                fields: vec![
+                    PolicyField::Hash(DataExpr::HexString(HexStringLiteral::new(
+                        "1234567890".to_string()
+                    ))),
+                    PolicyField::Script(DataExpr::HexString(HexStringLiteral::new(
+                        "1234567890".to_string()
+                    ))),
+                    PolicyField::Language(ScriptLanguageLiteral {
+                        value: ScriptLanguage::PlutusV3,
+                        span: Span::DUMMY,
+                    }),
+                ],
+                span: Span::DUMMY,
+            }),
+            span: Span::DUMMY,
+        }
+    );
+
+    input_to_ast_check!(
+        PolicyDef,
+        "policy_def_native",
+        "native_policy MyPolicy = all {
+            sig(Alice),
+            at_least(1) { sig(Bob), before(2000) },
+            after(1000),
+        };",
+        PolicyDef {
+            name: "MyPolicy".to_string(),
+            value: PolicyValue::Native(Box::new(crate::cardano::NativeScript::All(
+                crate::cardano::NativeScriptGroup {
+                    scripts: vec![
+                        crate::cardano::NativeScript::Sig(crate::cardano::NativeScriptCondition {
+                            value: DataExpr::Identifier(Identifier::new("Alice")),
+                            span: Span::DUMMY,
+                        }),
+                        crate::cardano::NativeScript::AtLeast(
+                            crate::cardano::NativeScriptAtLeast {
+                                required: 1,
+                                scripts: vec![
+                                    crate::cardano::NativeScript::Sig(
+                                        crate::cardano::NativeScriptCondition {
+                                            value: DataExpr::Identifier(Identifier::new("Bob")),
+                                            span: Span::DUMMY,
+                                        }
+                                    ),
+                                    crate::cardano::NativeScript::Before(
+                                        crate::cardano::NativeScriptCondition {
+                                            value: DataExpr::Number(2000),
+                                            span: Span::DUMMY,
+                                        }
+                                    ),
+                                ],
+                                span: Span::DUMMY,
+                            }
+                        ),
+                        crate::cardano::NativeScript::After(
+                            crate::cardano::NativeScriptCondition {
+                                value: DataExpr::Number(1000),
+                                span: Span::DUMMY,
+                            }
+                        ),
+                    ],
+                    span: Span::DUMMY,
+                }
+            ))),
+            span: Span::DUMMY,
+        }
+    );
+
     input_to_ast_check!(
         AssetDef,
         "hex_hex",
@@ -1918,6 +2043,27 @@ mod tests {
         }
     );
 
+    input_to_ast_check!(
+        OutputBlock,
+        "output_block_datum_hash",
+        r#"output {
+            to: my_party,
+            datum_hash: 0xAFAFAF,
+        }"#,
+        OutputBlock {
+            name: None,
+            fields: vec![
+                OutputBlockField::To(Box::new(AddressExpr::Identifier(Identifier::new(
+                    "my_party".to_string(),
+                )))),
+                OutputBlockField::DatumHash(Box::new(DataExpr::HexString(HexStringLiteral::new(
+                    "AFAFAF".to_string()
+                )))),
+            ],
+            span: Span::DUMMY,
+        }
+    );
+
     input_to_ast_check!(
         ChainSpecificBlock,
         "chain_specific_block_cardano",
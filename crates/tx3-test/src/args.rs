@@ -0,0 +1,508 @@
+//! Decoding of the JSON args of a request according to the type of each
+//! parameter of the tx.
+//!
+//! Values are expected in the following shapes:
+//!
+//! - `Int`: a JSON number or a string with a decimal number, for values that
+//!   don't fit in a JSON number.
+//! - `Bool`: a JSON boolean.
+//! - `Bytes`: a hex string.
+//! - `Address`: a bech32 address or the hex of its bytes.
+//! - `UtxoRef`: a string with the shape `<txid hex>#<index>`.
+//! - `List`: a JSON array.
+//! - custom types: an object with the `constructor` index and its `fields`.
+//!
+//! The TIR only carries the type of the params themselves. The types of list
+//! items and of the fields of custom types are taken from the source of the
+//! protocol when the tx comes from a template of the registry. Otherwise they
+//! are decoded by their JSON shape, and strings are rejected since they could
+//! stand for bytes, addresses or utxo refs alike.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tx3_cardano::pallas::ledger::addresses::Address;
+use tx3_lang::{ast, ir, ArgValue, ProtoTx, Protocol, UtxoRef};
+
+use crate::Error;
+
+/// The type of an arg, as far as it's known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgType {
+    Int,
+    Bool,
+    Bytes,
+    Address,
+    UtxoRef,
+    /// A list, along with the type of its items when known.
+    List(Option<Box<ArgType>>),
+    Custom(String),
+    Unsupported(String),
+}
+
+impl From<&ir::Type> for ArgType {
+    fn from(ty: &ir::Type) -> Self {
+        match ty {
+            ir::Type::Int => ArgType::Int,
+            ir::Type::Bool => ArgType::Bool,
+            ir::Type::Bytes => ArgType::Bytes,
+            ir::Type::Address => ArgType::Address,
+            ir::Type::UtxoRef => ArgType::UtxoRef,
+            ir::Type::List => ArgType::List(None),
+            ir::Type::Custom(name) => ArgType::Custom(name.clone()),
+            x => ArgType::Unsupported(format!("{x:?}")),
+        }
+    }
+}
+
+impl From<&ast::Type> for ArgType {
+    fn from(ty: &ast::Type) -> Self {
+        match ty {
+            ast::Type::Int => ArgType::Int,
+            ast::Type::Bool => ArgType::Bool,
+            ast::Type::Bytes => ArgType::Bytes,
+            ast::Type::Address => ArgType::Address,
+            ast::Type::UtxoRef => ArgType::UtxoRef,
+            ast::Type::List(item) => ArgType::List(Some(Box::new(item.as_ref().into()))),
+            ast::Type::Custom(name) => ArgType::Custom(name.value.clone()),
+            x => ArgType::Unsupported(format!("{x:?}")),
+        }
+    }
+}
+
+/// The types of the params of a tx, along with the custom types they use.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    params: BTreeMap<String, ArgType>,
+    /// The types of the fields of each case of a custom type, by type name.
+    types: BTreeMap<String, Vec<Vec<ArgType>>>,
+}
+
+impl Schema {
+    /// Takes the types known by the TIR, which leaves out the ones of list
+    /// items and of the fields of custom types.
+    pub fn from_tx(tx: &ProtoTx) -> Self {
+        let params = tx
+            .find_params()
+            .iter()
+            .map(|(name, ty)| (name.clone(), ty.into()))
+            .collect();
+
+        Self {
+            params,
+            types: BTreeMap::new(),
+        }
+    }
+
+    /// Takes the types of a template as declared in the source of its
+    /// protocol.
+    pub fn from_protocol(protocol: &Protocol, template: &str, tx: &ProtoTx) -> Self {
+        let mut schema = Self::from_tx(tx);
+
+        let declared = protocol
+            .txs()
+            .filter(|x| x.name == template)
+            .flat_map(|x| x.parameters.parameters.iter());
+
+        for param in declared {
+            if let Some(ty) = schema.params.get_mut(&param.name.to_lowercase()) {
+                *ty = (&param.r#type).into();
+            }
+        }
+
+        schema.types = protocol
+            .ast()
+            .types
+            .iter()
+            .map(|def| {
+                let cases = def
+                    .cases
+                    .iter()
+                    .map(|case| case.fields.iter().map(|x| (&x.r#type).into()).collect())
+                    .collect();
+
+                (def.name.clone(), cases)
+            })
+            .collect();
+
+        schema
+    }
+
+    fn decode(&self, ty: Option<&ArgType>, value: &Value) -> Result<ArgValue, String> {
+        let Some(ty) = ty else {
+            return self.decode_untyped(value);
+        };
+
+        match ty {
+            ArgType::Int => decode_int(value),
+            ArgType::Bool => decode_bool(value),
+            ArgType::Bytes => decode_bytes(value),
+            ArgType::Address => decode_address(value),
+            ArgType::UtxoRef => decode_utxo_ref(value),
+            ArgType::List(item) => self.decode_list(item.as_deref(), value),
+            ArgType::Custom(name) => self.decode_struct(Some(name), value),
+            ArgType::Unsupported(x) => Err(format!("args of type {x} are not supported")),
+        }
+    }
+
+    /// Decodes a value without knowing its type, only by its JSON shape.
+    fn decode_untyped(&self, value: &Value) -> Result<ArgValue, String> {
+        match value {
+            Value::Bool(_) => decode_bool(value),
+            Value::Number(_) => decode_int(value),
+            Value::String(_) => Err(format!(
+                "can't tell if {value} is bytes, an address or a utxo ref without the type \
+                 declared by the protocol"
+            )),
+            Value::Array(_) => self.decode_list(None, value),
+            Value::Object(_) => self.decode_struct(None, value),
+            Value::Null => Err("null is not a valid value".to_string()),
+        }
+    }
+
+    fn decode_list(&self, item: Option<&ArgType>, value: &Value) -> Result<ArgValue, String> {
+        let Value::Array(items) = value else {
+            return Err(format!("expected a list, got {value}"));
+        };
+
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                self.decode(item, x)
+                    .map_err(|err| format!("item #{i}: {err}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(ArgValue::List)
+    }
+
+    fn decode_struct(&self, name: Option<&String>, value: &Value) -> Result<ArgValue, String> {
+        let invalid = || format!("expected an object with a constructor and fields, got {value}");
+
+        let Value::Object(object) = value else {
+            return Err(invalid());
+        };
+
+        let constructor = object
+            .get("constructor")
+            .and_then(Value::as_u64)
+            .ok_or_else(invalid)? as usize;
+
+        let fields = match object.get("fields") {
+            Some(Value::Array(fields)) => fields.as_slice(),
+            None => &[],
+            Some(_) => return Err(invalid()),
+        };
+
+        let declared = name.and_then(|x| Some((x, self.types.get(x)?)));
+
+        let types = match declared {
+            Some((name, cases)) => {
+                let types = cases
+                    .get(constructor)
+                    .ok_or_else(|| format!("{name} has no constructor #{constructor}"))?;
+
+                if types.len() != fields.len() {
+                    return Err(format!(
+                        "constructor #{constructor} of {name} takes {} fields, got {}",
+                        types.len(),
+                        fields.len()
+                    ));
+                }
+
+                Some(types)
+            }
+            None => None,
+        };
+
+        let fields = fields
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let ty = types.map(|types| &types[i]);
+                self.decode(ty, x)
+                    .map_err(|err| format!("field #{i}: {err}"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ArgValue::Struct {
+            constructor,
+            fields,
+        })
+    }
+}
+
+fn decode_int(value: &Value) -> Result<ArgValue, String> {
+    let int = match value {
+        Value::Number(x) => x
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| x.as_u64().map(i128::from)),
+        Value::String(x) => x.parse::<i128>().ok(),
+        _ => None,
+    };
+
+    int.map(ArgValue::Int)
+        .ok_or_else(|| format!("expected an integer, got {value}"))
+}
+
+fn decode_bool(value: &Value) -> Result<ArgValue, String> {
+    match value {
+        Value::Bool(x) => Ok(ArgValue::Bool(*x)),
+        _ => Err(format!("expected a boolean, got {value}")),
+    }
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    hex::decode(value).map_err(|err| format!("invalid hex '{value}': {err}"))
+}
+
+fn decode_bytes(value: &Value) -> Result<ArgValue, String> {
+    match value {
+        Value::String(x) => decode_hex(x).map(ArgValue::Bytes),
+        _ => Err(format!("expected a hex string, got {value}")),
+    }
+}
+
+fn decode_address(value: &Value) -> Result<ArgValue, String> {
+    let Value::String(value) = value else {
+        return Err(format!("expected an address string, got {value}"));
+    };
+
+    let address = match Address::from_bech32(value) {
+        Ok(x) => x,
+        Err(_) => {
+            let bytes = decode_hex(value)
+                .map_err(|_| format!("'{value}' is not a bech32 or hex address"))?;
+
+            Address::from_bytes(&bytes)
+                .map_err(|err| format!("invalid address '{value}': {err}"))?
+        }
+    };
+
+    Ok(ArgValue::Address(address.to_vec()))
+}
+
+fn decode_utxo_ref(value: &Value) -> Result<ArgValue, String> {
+    let invalid = || format!("expected a utxo ref as '<txid>#<index>', got {value}");
+
+    let Value::String(x) = value else {
+        return Err(invalid());
+    };
+
+    let (txid, index) = x.split_once('#').ok_or_else(invalid)?;
+
+    let txid = decode_hex(txid)?;
+
+    if txid.len() != 32 {
+        return Err(format!("txid of {x} must be 32 bytes long"));
+    }
+
+    let index = index.parse().map_err(|_| invalid())?;
+
+    Ok(ArgValue::UtxoRef(UtxoRef { txid, index }))
+}
+
+/// Decodes the args of a request into the values expected by the params of
+/// the tx.
+pub fn decode_args(schema: &Schema, args: &Value) -> Result<BTreeMap<String, ArgValue>, Error> {
+    let args = match args {
+        Value::Object(x) => x,
+        Value::Null => return Ok(BTreeMap::new()),
+        x => {
+            return Err(Error::InvalidRequestForMethod(format!(
+                "args must be an object, got {x}"
+            )))
+        }
+    };
+
+    let mut out = BTreeMap::new();
+
+    for (key, value) in args {
+        let ty = schema.params.get(&key.to_lowercase()).ok_or_else(|| {
+            Error::InvalidArg(key.clone(), "not a parameter of the tx".to_string())
+        })?;
+
+        let value = schema
+            .decode(Some(ty), value)
+            .map_err(|err| Error::InvalidArg(key.clone(), err))?;
+
+        out.insert(key.clone(), value);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const ADDRESS: &str = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+    const PROTOCOL: &str = r#"
+        party Sender;
+
+        type Listing {
+            owner: Bytes,
+            price: Int,
+            tags: List<Bytes>,
+        }
+
+        tx list(listing: Listing, owners: List<Address>) {
+            input source {
+                from: Sender,
+                min_amount: fees,
+            }
+
+            output {
+                to: Sender,
+                amount: source - fees,
+                datum: listing,
+            }
+
+            output {
+                to: Sender,
+                amount: Ada(0),
+                datum: owners,
+            }
+        }
+    "#;
+
+    fn schema() -> Schema {
+        let params = [
+            ("quantity", ir::Type::Int),
+            ("sender", ir::Type::Address),
+            ("locked", ir::Type::UtxoRef),
+            ("password", ir::Type::Bytes),
+            ("state", ir::Type::Custom("State".to_string())),
+        ];
+
+        Schema {
+            params: params
+                .iter()
+                .map(|(name, ty)| (name.to_string(), ty.into()))
+                .collect(),
+            types: BTreeMap::new(),
+        }
+    }
+
+    fn protocol_schema() -> Schema {
+        let protocol = Protocol::from_string(PROTOCOL.to_string()).load().unwrap();
+        let tx = protocol.new_tx("list").unwrap();
+
+        Schema::from_protocol(&protocol, "list", &tx)
+    }
+
+    #[test]
+    fn decodes_typed_args() {
+        let args = json!({
+            "quantity": "-170141183460469231731687303715884105728",
+            "Sender": ADDRESS,
+            "locked": "682d6d95495403b491737b95dae5c1f060498d9efc91a592962134f880398be2#1",
+            "password": "0xabc1",
+            "state": { "constructor": 0, "fields": [1, true, [2, 3]] },
+        });
+
+        let args = decode_args(&schema(), &args).unwrap();
+
+        assert!(matches!(args["quantity"], ArgValue::Int(i128::MIN)));
+        assert!(matches!(&args["Sender"], ArgValue::Address(x) if x.len() == 57));
+        assert!(matches!(&args["locked"], ArgValue::UtxoRef(x) if x.index == 1));
+        assert!(matches!(&args["password"], ArgValue::Bytes(x) if x == &[0xab, 0xc1]));
+
+        let ArgValue::Struct {
+            constructor,
+            fields,
+        } = &args["state"]
+        else {
+            panic!("expected a struct");
+        };
+
+        assert_eq!(*constructor, 0);
+        assert!(matches!(
+            fields[..],
+            [ArgValue::Int(1), ArgValue::Bool(true), ArgValue::List(_)]
+        ));
+    }
+
+    #[test]
+    fn reports_the_invalid_arg() {
+        let cases = [
+            (json!({ "quantity": 1.5 }), "quantity"),
+            (json!({ "sender": "not-an-address" }), "sender"),
+            (json!({ "locked": "abcd#0" }), "locked"),
+            (json!({ "password": "xyz" }), "password"),
+            (json!({ "unknown": 1 }), "unknown"),
+        ];
+
+        for (args, expected) in cases {
+            match decode_args(&schema(), &args) {
+                Err(Error::InvalidArg(key, _)) => assert_eq!(key, expected),
+                x => panic!("expected an invalid arg error, got {x:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_fields_with_declared_types() {
+        let args = json!({
+            "listing": { "constructor": 0, "fields": ["abcd", 5, ["01", "02"]] },
+            "owners": [ADDRESS],
+        });
+
+        let args = decode_args(&protocol_schema(), &args).unwrap();
+
+        let ArgValue::Struct { fields, .. } = &args["listing"] else {
+            panic!("expected a struct");
+        };
+
+        assert!(matches!(&fields[0], ArgValue::Bytes(x) if x == &[0xab, 0xcd]));
+        assert!(matches!(fields[1], ArgValue::Int(5)));
+        assert!(
+            matches!(&fields[2], ArgValue::List(x) if matches!(x[..], [ArgValue::Bytes(_), ArgValue::Bytes(_)]))
+        );
+
+        assert!(
+            matches!(&args["owners"], ArgValue::List(x) if matches!(x[..], [ArgValue::Address(_)]))
+        );
+    }
+
+    #[test]
+    fn rejects_strings_of_unknown_type() {
+        let protocol = Protocol::from_string(PROTOCOL.to_string()).load().unwrap();
+        let tx = protocol.new_tx("list").unwrap();
+
+        // the TIR alone doesn't say what the fields of the listing are
+        let args = json!({
+            "listing": { "constructor": 0, "fields": ["abcd", 5, []] },
+        });
+
+        match decode_args(&Schema::from_tx(&tx), &args) {
+            Err(Error::InvalidArg(key, err)) => {
+                assert_eq!(key, "listing");
+                assert!(err.starts_with("field #0: can't tell"), "{err}");
+            }
+            x => panic!("expected an invalid arg error, got {x:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_mismatched_fields() {
+        let cases = [
+            json!({ "listing": { "constructor": 1, "fields": [] } }),
+            json!({ "listing": { "constructor": 0, "fields": ["abcd", 5] } }),
+            json!({ "listing": { "constructor": 0, "fields": ["abcd", "5x", []] } }),
+            json!({ "owners": ["not-an-address"] }),
+        ];
+
+        for args in cases {
+            assert!(matches!(
+                decode_args(&protocol_schema(), &args),
+                Err(Error::InvalidArg(_, _))
+            ));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Configuration of the server, read from a TOML file and overridden by
+//! `TRP_` prefixed env vars, using `__` to reach nested keys (e.g.
+//! `TRP_LEDGER__ENDPOINT_URL`).
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{ledger::LedgerConfig, Error};
+
+/// File read when no config path is given, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "trp.toml";
+
+fn default_listen_address() -> String {
+    "0.0.0.0:8000".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Config {
+    #[serde(default = "default_listen_address")]
+    pub listen_address: String,
+
+    pub ledger: LedgerConfig,
+
+    /// Dir of `.tx3` protocols to serve by name.
+    #[serde(default)]
+    pub protocols_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config from the given file, which must exist, or from the
+    /// default file when there's one.
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let file = match path {
+            Some(path) => ::config::File::from(path).required(true),
+            None => ::config::File::with_name(DEFAULT_CONFIG_FILE).required(false),
+        };
+
+        Self::load_from(file.format(::config::FileFormat::Toml), env_source())
+    }
+
+    fn load_from(
+        file: impl ::config::Source + Send + Sync + 'static,
+        env: ::config::Environment,
+    ) -> Result<Self, Error> {
+        ::config::Config::builder()
+            .add_source(file)
+            .add_source(env)
+            .build()
+            .and_then(|x| x.try_deserialize())
+            .map_err(|err| Error::Config(err.to_string()))
+    }
+}
+
+fn env_source() -> ::config::Environment {
+    ::config::Environment::with_prefix("TRP")
+        .prefix_separator("_")
+        .separator("__")
+        .try_parsing(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(toml: &str, env: &[(&str, &str)]) -> Result<Config, Error> {
+        let file = ::config::File::from_str(toml, ::config::FileFormat::Toml);
+
+        let env: HashMap<_, _> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        Config::load_from(file, env_source().source(Some(env)))
+    }
+
+    #[test]
+    fn loads_each_ledger_backend() {
+        let config = load(
+            r#"
+            [ledger]
+            type = "u5c"
+            endpoint_url = "https://example.com"
+            api_key = "key"
+            network_id = 0
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_address, "0.0.0.0:8000");
+        assert!(matches!(config.ledger, LedgerConfig::U5c(x) if x.network_id == 0));
+
+        let config = load(
+            r#"
+            listen_address = "127.0.0.1:9000"
+            protocols_dir = "protocols"
+
+            [ledger]
+            type = "emulator"
+            funds = [{ address = "addr1", lovelace = 10 }]
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_address, "127.0.0.1:9000");
+        assert_eq!(config.protocols_dir, Some(PathBuf::from("protocols")));
+        assert!(matches!(config.ledger, LedgerConfig::Emulator { funds } if funds.len() == 1));
+
+        let config = load("ledger = { type = \"file\", path = \"state.json\" }", &[]).unwrap();
+        assert!(matches!(config.ledger, LedgerConfig::File { .. }));
+
+        let config = load("ledger = { type = \"mock\" }", &[]).unwrap();
+        assert!(matches!(config.ledger, LedgerConfig::Mock));
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let config = load(
+            "ledger = { type = \"file\", path = \"state.json\" }",
+            &[
+                ("TRP_LISTEN_ADDRESS", "127.0.0.1:1234"),
+                ("TRP_LEDGER__PATH", "other.json"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_address, "127.0.0.1:1234");
+        assert!(
+            matches!(config.ledger, LedgerConfig::File { path } if path == Path::new("other.json"))
+        );
+    }
+
+    #[test]
+    fn requires_a_ledger() {
+        assert!(matches!(load("", &[]), Err(Error::Config(_))));
+    }
+}
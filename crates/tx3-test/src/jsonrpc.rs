@@ -0,0 +1,230 @@
+//! JSON-RPC 2.0 envelopes for the requests and responses of the server.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub use tx3_trp::spec::{
+    INPUTS_NOT_RESOLVED, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND,
+    PARSE_ERROR, RESOLVE_ERROR, SUBMIT_ERROR,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Id {
+    Number(serde_json::Number),
+    String(String),
+    Null,
+}
+
+#[derive(Debug)]
+pub struct Request {
+    /// The id of the request, `None` for notifications.
+    pub id: Option<Id>,
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorObject {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ErrorObject {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(self, data: Value) -> Self {
+        Self {
+            data: Some(data),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Id,
+    #[serde(flatten)]
+    pub payload: Payload,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Payload {
+    Result(Value),
+    Error(ErrorObject),
+}
+
+impl Response {
+    pub fn result(id: Id, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            payload: Payload::Result(result),
+        }
+    }
+
+    pub fn error(id: Id, error: ErrorObject) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            payload: Payload::Error(error),
+        }
+    }
+}
+
+/// A call from the server to the client, which doesn't expect a response.
+#[derive(Serialize, Debug)]
+pub struct Notification {
+    pub jsonrpc: &'static str,
+    pub method: &'static str,
+    pub params: Value,
+}
+
+impl Notification {
+    pub fn new(method: &'static str, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            method,
+            params,
+        }
+    }
+}
+
+/// The body of an HTTP request, either a single call or a batch of them.
+#[derive(Debug)]
+pub enum Body {
+    Single(Value),
+    Batch(Vec<Value>),
+}
+
+pub fn parse_body(bytes: &[u8]) -> Result<Body, ErrorObject> {
+    let body: Value = serde_json::from_slice(bytes)
+        .map_err(|err| ErrorObject::new(PARSE_ERROR, format!("parse error: {err}")))?;
+
+    match body {
+        Value::Array(x) if x.is_empty() => Err(ErrorObject::new(INVALID_REQUEST, "empty batch")),
+        Value::Array(x) => Ok(Body::Batch(x)),
+        x => Ok(Body::Single(x)),
+    }
+}
+
+/// Parses a single call of the body. On failure, the error comes along with
+/// the id of the request when it could be read, so that the error response
+/// can still be matched by the client.
+pub fn parse_request(value: Value) -> Result<Request, (Id, ErrorObject)> {
+    let invalid = |id: Id, message: &str| {
+        Err((
+            id,
+            ErrorObject::new(INVALID_REQUEST, format!("invalid request: {message}")),
+        ))
+    };
+
+    let Value::Object(mut object) = value else {
+        return invalid(Id::Null, "expected an object");
+    };
+
+    let id = match object.remove("id") {
+        None => None,
+        Some(x) => match serde_json::from_value::<Id>(x) {
+            Ok(x) => Some(x),
+            Err(_) => return invalid(Id::Null, "id must be a string, a number or null"),
+        },
+    };
+
+    let error_id = id.clone().unwrap_or(Id::Null);
+
+    if object.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return invalid(error_id, "jsonrpc must be \"2.0\"");
+    }
+
+    let method = match object.remove("method") {
+        Some(Value::String(x)) => x,
+        _ => return invalid(error_id, "method must be a string"),
+    };
+
+    let params = match object.remove("params") {
+        None => Value::Null,
+        Some(x @ (Value::Object(_) | Value::Array(_))) => x,
+        Some(_) => return invalid(error_id, "params must be an object or an array"),
+    };
+
+    Ok(Request { id, method, params })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_ids_and_notifications() {
+        let request = |id: Value| {
+            parse_request(json!({ "jsonrpc": "2.0", "method": "trp.resolve", "id": id }))
+                .unwrap()
+                .id
+        };
+
+        assert_eq!(request(json!(1)), Some(Id::Number(1.into())));
+        assert_eq!(request(json!("a")), Some(Id::String("a".into())));
+        assert_eq!(request(Value::Null), Some(Id::Null));
+
+        let notification =
+            parse_request(json!({ "jsonrpc": "2.0", "method": "trp.resolve" })).unwrap();
+
+        assert_eq!(notification.id, None);
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let cases = [
+            json!(1),
+            json!({ "method": "trp.resolve", "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": 1, "id": 1 }),
+            json!({ "jsonrpc": "2.0", "method": "trp.resolve", "id": [1] }),
+            json!({ "jsonrpc": "2.0", "method": "trp.resolve", "params": 1 }),
+        ];
+
+        for case in cases {
+            let (_, err) = parse_request(case).unwrap_err();
+            assert_eq!(err.code, INVALID_REQUEST);
+        }
+
+        assert_eq!(parse_body(b"{").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(parse_body(b"[]").unwrap_err().code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn serializes_envelopes() {
+        let ok = Response::result(Id::Number(1.into()), json!({ "tx": "00" }));
+
+        assert_eq!(
+            serde_json::to_value(ok).unwrap(),
+            json!({ "jsonrpc": "2.0", "id": 1, "result": { "tx": "00" } })
+        );
+
+        let err = Response::error(
+            Id::Null,
+            ErrorObject::new(METHOD_NOT_FOUND, "unknown").with_data(json!({ "method": "x" })),
+        );
+
+        assert_eq!(
+            serde_json::to_value(err).unwrap(),
+            json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32601, "message": "unknown", "data": { "method": "x" } }
+            })
+        );
+    }
+}
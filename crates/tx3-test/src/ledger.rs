@@ -0,0 +1,146 @@
+//! Ledger backends the server can be configured with.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tx3_cardano::{
+    ledgers::{emulator::EmulatorLedger, file::FileLedger, mock::MockLedger, u5c},
+    pallas::ledger::addresses::Address,
+    resolve::Ledger,
+    submit::Submitter,
+    ChainPoint, PParams, SlotConfig,
+};
+use tx3_lang::{ir::InputQuery, UtxoRef, UtxoSet};
+
+use crate::Error;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Funds {
+    /// Bech32 address to fund.
+    pub address: String,
+    pub lovelace: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LedgerConfig {
+    /// A UTxO RPC endpoint.
+    U5c(u5c::Config),
+
+    /// An in-memory ledger, starting with the given funds.
+    Emulator {
+        #[serde(default)]
+        funds: Vec<Funds>,
+    },
+
+    /// An in-memory ledger, starting with the state of a JSON or YAML fixture.
+    File { path: PathBuf },
+
+    /// A fixed ledger that resolves every query to the same UTxO and doesn't
+    /// accept txs, meant for testing clients.
+    Mock,
+}
+
+#[derive(Clone)]
+pub enum AnyLedger {
+    U5c(u5c::Ledger),
+    Emulator(EmulatorLedger),
+    File(FileLedger),
+    Mock(MockLedger),
+}
+
+impl AnyLedger {
+    pub async fn connect(config: LedgerConfig) -> Result<Self, Error> {
+        match config {
+            LedgerConfig::U5c(config) => {
+                let ledger = u5c::Ledger::new(config)
+                    .await
+                    .map_err(|err| Error::LedgerError(err.to_string()))?;
+
+                Ok(Self::U5c(ledger))
+            }
+            LedgerConfig::Emulator { funds } => {
+                let ledger = EmulatorLedger::default();
+
+                for funds in funds {
+                    let address = Address::from_bech32(&funds.address).map_err(|err| {
+                        Error::Config(format!("invalid address {}: {err}", funds.address))
+                    })?;
+
+                    ledger.fund(&address.to_vec(), funds.lovelace);
+                }
+
+                Ok(Self::Emulator(ledger))
+            }
+            LedgerConfig::File { path } => {
+                let ledger =
+                    FileLedger::from_file(&path).map_err(|err| Error::Config(err.to_string()))?;
+
+                Ok(Self::File(ledger))
+            }
+            LedgerConfig::Mock => Ok(Self::Mock(MockLedger)),
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($self:ident, $ledger:ident => $call:expr) => {
+        match $self {
+            AnyLedger::U5c($ledger) => $call,
+            AnyLedger::Emulator($ledger) => $call,
+            AnyLedger::File($ledger) => $call,
+            AnyLedger::Mock($ledger) => $call,
+        }
+    };
+}
+
+impl Ledger for AnyLedger {
+    async fn get_pparams(&self) -> Result<PParams, tx3_cardano::Error> {
+        delegate!(self, x => x.get_pparams().await)
+    }
+
+    async fn resolve_input(&self, query: &InputQuery) -> Result<UtxoSet, tx3_cardano::Error> {
+        delegate!(self, x => x.resolve_input(query).await)
+    }
+
+    async fn resolve_collateral(&self, query: &InputQuery) -> Result<UtxoSet, tx3_cardano::Error> {
+        delegate!(self, x => x.resolve_collateral(query).await)
+    }
+
+    async fn resolve_datum(&self, hash: &[u8]) -> Result<Option<Vec<u8>>, tx3_cardano::Error> {
+        delegate!(self, x => x.resolve_datum(hash).await)
+    }
+
+    async fn read_utxos(&self, refs: &[UtxoRef]) -> Result<UtxoSet, tx3_cardano::Error> {
+        delegate!(self, x => x.read_utxos(refs).await)
+    }
+
+    async fn get_tip(&self) -> Result<ChainPoint, tx3_cardano::Error> {
+        delegate!(self, x => x.get_tip().await)
+    }
+
+    async fn get_slot_config(&self) -> Result<SlotConfig, tx3_cardano::Error> {
+        delegate!(self, x => x.get_slot_config().await)
+    }
+}
+
+impl Submitter for AnyLedger {
+    async fn submit_tx(&self, payload: &[u8]) -> Result<Vec<u8>, tx3_cardano::Error> {
+        match self {
+            AnyLedger::U5c(x) => x.submit_tx(payload).await,
+            AnyLedger::Emulator(x) => x.submit_tx(payload).await,
+            AnyLedger::File(x) => x.emulator().submit_tx(payload).await,
+            AnyLedger::Mock(_) => Err(tx3_cardano::Error::LedgerInternalError(
+                "the mock ledger doesn't accept txs".to_string(),
+            )),
+        }
+    }
+
+    async fn wait_for_confirmation(&self, hash: &[u8]) -> Result<(), tx3_cardano::Error> {
+        match self {
+            AnyLedger::U5c(x) => x.wait_for_confirmation(hash).await,
+            AnyLedger::Emulator(x) => x.wait_for_confirmation(hash).await,
+            AnyLedger::File(x) => x.emulator().wait_for_confirmation(hash).await,
+            AnyLedger::Mock(_) => Err(tx3_cardano::Error::TxNotFound(hex::encode(hash))),
+        }
+    }
+}
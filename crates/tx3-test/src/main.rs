@@ -10,13 +10,27 @@
 //! The JSON-RPC server is implemented as a Warp application and adheres to
 //! the JSON-RPC 2.0 spec.
 
-use base64::Engine;
-use serde::{Deserialize, Serialize};
+mod args;
+mod config;
+mod jsonrpc;
+mod ledger;
+mod registry;
+mod ws;
+
+use crate::config::Config;
+use clap::Parser as _;
+use jsonrpc::{Body, ErrorObject, Id, Request, Response};
+use ledger::AnyLedger;
+use registry::Registry;
+use serde::{de::DeserializeOwned, Deserialize};
 use serde_json::json;
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error};
-use warp::Filter as _;
+use tx3_cardano::{ResolveOptions, Submitter as _};
+use tx3_trp::spec::{Encoding, IrEnvelope, ResolveParams, ResolveResult, TxEnvelope, TxSource};
+use warp::{http::StatusCode, Filter, Reply as _};
+use ws::Progress;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -32,6 +46,9 @@ pub enum Error {
     #[error("invalid ir: {0}")]
     InvalidIr(String),
 
+    #[error("incompatible ir: {0}")]
+    IncompatibleIr(tx3_lang::migrating::Error),
+
     #[error("resolve error: {0}")]
     ResolveError(#[from] tx3_cardano::Error),
 
@@ -40,154 +57,379 @@ pub enum Error {
 
     #[error("invalid arg value {0}: {1}")]
     InvalidArg(String, String),
-}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Config {
-    pub listen_address: String,
-    pub ledger: tx3_cardano::ledgers::u5c::Config,
+    #[error("missing arg {0}")]
+    MissingArg(String),
+
+    #[error("invalid tx: {0}")]
+    InvalidTx(String),
+
+    #[error("submit error: {0}")]
+    SubmitError(tx3_cardano::Error),
+
+    #[error("unknown protocol: {0}")]
+    UnknownProtocol(String),
+
+    #[error("unknown tx template: {0}")]
+    UnknownTemplate(String),
+
+    #[error("invalid tx template {0}: {1}")]
+    InvalidTemplate(String, String),
+
+    #[error("invalid protocol {0}: {1}")]
+    InvalidProtocol(String, String),
 }
 
-#[derive(Deserialize)]
-struct AnyRequest {
-    pub id: Option<String>,
-    pub method: String,
-    pub params: serde_json::Value,
+/// The state shared by all the requests.
+#[derive(Clone)]
+pub struct Context {
+    pub ledger: AnyLedger,
+    pub registry: Arc<Registry>,
 }
 
 #[derive(Deserialize)]
-enum IrEncoding {
-    #[serde(rename = "base64")]
-    Base64,
-    #[serde(rename = "hex")]
-    Hex,
+struct DescribeRequest {
+    pub protocol: String,
 }
 
 #[derive(Deserialize)]
-struct IrEnvelope {
-    #[allow(dead_code)]
-    pub version: String,
-
-    pub bytecode: String,
-    pub encoding: IrEncoding,
+struct ParamsRequest {
+    #[serde(flatten)]
+    pub source: TxSource,
 }
 
 #[derive(Deserialize)]
-struct ResolveProtoTxRequest {
-    pub tir: IrEnvelope,
-    pub args: serde_json::Value,
+struct SubmitRequest {
+    pub tx: TxEnvelope,
 }
 
-impl TryFrom<AnyRequest> for ResolveProtoTxRequest {
-    type Error = Error;
+fn parse_params<T: DeserializeOwned>(request: Request) -> Result<T, Error> {
+    serde_json::from_value(request.params)
+        .map_err(|x| Error::InvalidRequestForMethod(x.to_string()))
+}
 
-    fn try_from(request: AnyRequest) -> Result<Self, Self::Error> {
-        serde_json::from_value(request.params)
-            .map_err(|x| Error::InvalidRequestForMethod(x.to_string()))
+impl From<Error> for ErrorObject {
+    fn from(err: Error) -> Self {
+        let message = err.to_string();
+
+        match err {
+            Error::InvalidRequestForMethod(_) | Error::InvalidIr(_) | Error::InvalidTx(_) => {
+                ErrorObject::new(jsonrpc::INVALID_PARAMS, message)
+            }
+            Error::InvalidArg(arg, reason) => ErrorObject::new(jsonrpc::INVALID_PARAMS, message)
+                .with_data(json!({ "arg": arg, "reason": reason })),
+            Error::MissingArg(arg)
+            | Error::ResolveError(tx3_cardano::Error::ArgNotAssigned(arg)) => {
+                ErrorObject::new(jsonrpc::INVALID_PARAMS, message).with_data(json!({ "arg": arg }))
+            }
+            Error::IncompatibleIr(tx3_lang::migrating::Error::UnsupportedVersion {
+                found,
+                supported,
+            }) => ErrorObject::new(jsonrpc::INVALID_PARAMS, message)
+                .with_data(json!({ "version": found, "supported": supported })),
+            Error::IncompatibleIr(_) => ErrorObject::new(jsonrpc::INVALID_PARAMS, message),
+            Error::UnknownMethod(method) => ErrorObject::new(jsonrpc::METHOD_NOT_FOUND, message)
+                .with_data(json!({ "method": method })),
+            Error::ResolveError(tx3_cardano::Error::InputsNotResolved(input, query)) => {
+                ErrorObject::new(jsonrpc::INPUTS_NOT_RESOLVED, message)
+                    .with_data(json!({ "input": input, "query": query }))
+            }
+            Error::ResolveError(_) => ErrorObject::new(jsonrpc::RESOLVE_ERROR, message),
+            Error::SubmitError(_) => ErrorObject::new(jsonrpc::SUBMIT_ERROR, message),
+            Error::UnknownProtocol(_)
+            | Error::UnknownTemplate(_)
+            | Error::InvalidTemplate(_, _)
+            | Error::InvalidProtocol(_, _) => ErrorObject::new(jsonrpc::INVALID_PARAMS, message),
+            Error::Config(_) | Error::LedgerError(_) => {
+                ErrorObject::new(jsonrpc::INTERNAL_ERROR, message)
+            }
+        }
     }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+fn decode_tir(tir: &IrEnvelope) -> Result<tx3_lang::ProtoTx, Error> {
+    let tx = match tir.encoding {
+        Encoding::Json => tx3_lang::ProtoTx::from_ir_json(&tir.version, &tir.bytecode),
+        _ => {
+            let bytes = tir
+                .encoding
+                .decode(&tir.bytecode)
+                .map_err(Error::InvalidIr)?;
+
+            tx3_lang::ProtoTx::from_versioned_ir_bytes(&tir.version, &bytes)
+        }
+    };
+
+    tx.map_err(|x| match x {
+        tx3_lang::migrating::Error::UnsupportedVersion { .. } => Error::IncompatibleIr(x),
+        x => Error::InvalidIr(x.to_string()),
+    })
 }
 
-impl From<Error> for ErrorResponse {
-    fn from(err: Error) -> Self {
-        ErrorResponse {
-            error: err.to_string(),
+/// Loads the tx of a request, either decoding its TIR or taking a template
+/// from the registry, along with the types of its params.
+fn load_source(
+    registry: &Registry,
+    source: &TxSource,
+) -> Result<(tx3_lang::ProtoTx, args::Schema), Error> {
+    match source {
+        TxSource::Tir { tir } => {
+            let tx = decode_tir(tir)?;
+            let schema = args::Schema::from_tx(&tx);
+            Ok((tx, schema))
         }
+        TxSource::Template { protocol, template } => registry.new_tx(protocol, template),
     }
 }
 
-fn parse_request(body: serde_json::Value) -> Result<AnyRequest, ErrorResponse> {
-    match serde_json::from_value(body) {
-        Ok(x) => Ok(x),
-        Err(x) => Err(ErrorResponse {
-            error: x.to_string(),
-        }),
+/// Loads the tx of the request and applies its args, checking that every
+/// param of the tx gets a value.
+fn load_proto_tx(registry: &Registry, request: &ResolveParams) -> Result<tx3_lang::ProtoTx, Error> {
+    let (mut tx, schema) = load_source(registry, &request.source)?;
+
+    let args = args::decode_args(&schema, &request.args)?;
+
+    if let Some(missing) = tx
+        .find_params()
+        .keys()
+        .find(|x| !args.keys().any(|k| k.eq_ignore_ascii_case(x)))
+    {
+        return Err(Error::MissingArg(missing.clone()));
+    }
+
+    for (key, val) in args {
+        tx.set_arg(&key, val);
     }
+
+    Ok(tx)
 }
 
-async fn handle_resolve_proto_tx(
-    ledger: tx3_cardano::ledgers::u5c::Ledger,
-    request: AnyRequest,
-) -> Result<warp::reply::Json, Error> {
-    let request = ResolveProtoTxRequest::try_from(request)?;
-
-    let tx = match request.tir.encoding {
-        IrEncoding::Base64 => base64::engine::general_purpose::STANDARD
-            .decode(request.tir.bytecode)
-            .map_err(|x| Error::InvalidIr(x.to_string()))?,
-        IrEncoding::Hex => {
-            hex::decode(request.tir.bytecode).map_err(|x| Error::InvalidIr(x.to_string()))?
-        }
+/// Resolves the tx against the ledger, reporting each step to `progress`.
+async fn resolve_tx(
+    context: Context,
+    tx: tx3_lang::ProtoTx,
+    progress: &Progress,
+) -> Result<tx3_cardano::resolve::TxEval, Error> {
+    let options = ResolveOptions {
+        max_optimize_rounds: 5,
+        ..Default::default()
     };
 
-    let mut tx =
-        tx3_lang::ProtoTx::from_ir_bytes(&tx).map_err(|x| Error::InvalidIr(x.to_string()))?;
+    let tx =
+        tx3_cardano::resolve_tx_with_progress(tx, context.ledger, &options, |x| progress.report(x))
+            .await?;
 
-    for (key, val) in request.args.as_object().unwrap().iter() {
-        match val {
-            serde_json::Value::String(x) => tx.set_arg(key, x.as_str().into()),
-            serde_json::Value::Number(x) => tx.set_arg(key, x.as_i64().unwrap().into()),
-            _ => return Err(Error::InvalidArg(key.to_string(), val.to_string())),
-        }
+    Ok(tx)
+}
+
+async fn handle_resolve_proto_tx(
+    context: Context,
+    request: Request,
+    progress: &Progress,
+) -> Result<serde_json::Value, Error> {
+    let request: ResolveParams = parse_params(request)?;
+    let tx = load_proto_tx(&context.registry, &request)?;
+
+    let tx = resolve_tx(context, tx, progress).await?;
+
+    Ok(json!(ResolveResult {
+        tx: hex::encode(tx.payload)
+    }))
+}
+
+async fn handle_params(context: Context, request: Request) -> Result<serde_json::Value, Error> {
+    let request: ParamsRequest = parse_params(request)?;
+    let (tx, _) = load_source(&context.registry, &request.source)?;
+
+    Ok(json!({
+        "params": tx.find_params(),
+        "queries": tx.find_queries(),
+    }))
+}
+
+async fn handle_evaluate(
+    context: Context,
+    request: Request,
+    progress: &Progress,
+) -> Result<serde_json::Value, Error> {
+    let request: ResolveParams = parse_params(request)?;
+    let tx = load_proto_tx(&context.registry, &request)?;
+
+    let tx = resolve_tx(context, tx, progress).await?;
+
+    #[allow(unused_mut)]
+    let mut result = json!({
+        "fee": tx.fee,
+        "size": tx.payload.len(),
+    });
+
+    // without phase-2 evaluation the units are just the placeholders set
+    // while compiling, so they're not worth reporting
+    #[cfg(feature = "phase2")]
+    {
+        result["exUnits"] = json!(tx.ex_units);
     }
 
-    let tx = tx3_cardano::resolve_tx(tx, ledger, 5).await?;
+    Ok(result)
+}
+
+async fn handle_list_protocols(context: Context) -> Result<serde_json::Value, Error> {
+    Ok(context.registry.list())
+}
 
-    let reply = json!({ "tx": hex::encode(tx.payload) });
+async fn handle_describe(context: Context, request: Request) -> Result<serde_json::Value, Error> {
+    let request: DescribeRequest = parse_params(request)?;
 
-    Ok(warp::reply::json(&reply))
+    context.registry.describe(&request.protocol)
 }
 
-pub async fn handle_request(
-    ledger: tx3_cardano::ledgers::u5c::Ledger,
-    body: serde_json::Value,
-) -> warp::reply::Json {
-    let request = match parse_request(body) {
+async fn handle_submit(context: Context, request: Request) -> Result<serde_json::Value, Error> {
+    let request: SubmitRequest = parse_params(request)?;
+
+    let payload = request
+        .tx
+        .encoding
+        .decode(&request.tx.payload)
+        .map_err(Error::InvalidTx)?;
+
+    let hash = context
+        .ledger
+        .submit_tx(&payload)
+        .await
+        .map_err(Error::SubmitError)?;
+
+    Ok(json!({ "hash": hex::encode(hash) }))
+}
+
+/// Handles a single call, returning `None` for notifications.
+async fn handle_call(
+    context: Context,
+    call: serde_json::Value,
+    progress: &Progress,
+) -> Option<Response> {
+    let request = match jsonrpc::parse_request(call) {
         Ok(x) => x,
-        Err(err) => return warp::reply::json(&err),
+        Err((id, err)) => return Some(Response::error(id, err)),
     };
 
-    debug!(id = request.id, method = request.method, "handling request");
+    debug!(id = ?request.id, method = request.method, "handling request");
+
+    let id = request.id.clone();
+    let progress = progress.for_request(id.clone());
 
     let result = match request.method.as_str() {
-        "trp.resolve" => handle_resolve_proto_tx(ledger, request).await,
+        "trp.resolve" => handle_resolve_proto_tx(context, request, &progress).await,
+        "trp.params" => handle_params(context, request).await,
+        "trp.evaluate" => handle_evaluate(context, request, &progress).await,
+        "trp.submit" => handle_submit(context, request).await,
+        "trp.listProtocols" => handle_list_protocols(context).await,
+        "trp.describe" => handle_describe(context, request).await,
         x => Err(Error::UnknownMethod(x.to_string())),
     };
 
+    if let Err(err) = &result {
+        error!(?id, %err, "request failed");
+    }
+
+    // notifications don't get a response, not even for errors
+    let id = id?;
+
     match result {
-        Ok(x) => x,
-        Err(err) => warp::reply::json(&ErrorResponse::from(err)),
+        Ok(x) => Some(Response::result(id, x)),
+        Err(err) => Some(Response::error(id, err.into())),
+    }
+}
+
+/// Handles a JSON-RPC body, either a single call or a batch, returning
+/// `None` when there's nothing to reply.
+pub async fn handle_body(
+    context: Context,
+    body: &[u8],
+    progress: &Progress,
+) -> Option<serde_json::Value> {
+    match jsonrpc::parse_body(body) {
+        Ok(Body::Single(call)) => handle_call(context, call, progress).await.map(|x| json!(x)),
+        Ok(Body::Batch(calls)) => {
+            let mut responses = vec![];
+
+            for call in calls {
+                responses.extend(handle_call(context.clone(), call, progress).await);
+            }
+
+            (!responses.is_empty()).then(|| json!(responses))
+        }
+        Err(err) => Some(json!(Response::error(Id::Null, err))),
     }
 }
 
+pub async fn handle_request(
+    context: Context,
+    body: warp::hyper::body::Bytes,
+) -> warp::reply::Response {
+    // HTTP clients can't be notified of the progress of their requests
+    let reply = handle_body(context, &body, &Progress::default()).await;
+
+    match reply {
+        Some(x) => warp::reply::json(&x).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// JSON-RPC over WebSocket at `/ws` and over HTTP posts at any other path.
+pub fn routes(
+    context: Context,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let with_context = warp::any().map(move || context.clone());
+
+    let ws = warp::path("ws")
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(with_context.clone())
+        .map(|ws: warp::ws::Ws, context: Context| {
+            ws.on_upgrade(move |socket| ws::handle_socket(context, socket))
+        });
+
+    let http = with_context
+        .and(warp::post())
+        .and(warp::body::bytes())
+        .then(handle_request);
+
+    ws.or(http)
+}
+
 pub async fn serve(config: Config, cancel: CancellationToken) -> Result<(), Error> {
-    let ledger = tx3_cardano::ledgers::u5c::Ledger::new(config.ledger)
-        .await
-        .unwrap();
+    let ledger = AnyLedger::connect(config.ledger).await?;
+
+    let registry = match &config.protocols_dir {
+        Some(dir) => Registry::load(dir)?,
+        None => Registry::default(),
+    };
+
+    let registry = Arc::new(registry);
+
+    tokio::spawn(
+        registry
+            .clone()
+            .watch(Duration::from_secs(2), cancel.clone()),
+    );
+
+    let context = Context { ledger, registry };
 
     let cors = warp::cors()
         .allow_any_origin()
         .allow_headers(vec!["content-type"])
         .allow_methods(vec!["POST", "GET", "OPTIONS"]);
 
-    let filter = warp::any()
-        .map(move || ledger.clone())
-        .and(warp::post())
-        .and(warp::body::json())
-        .then(handle_request)
-        .with(cors);
+    let filter = routes(context).with(cors);
 
     let address: SocketAddr = config
         .listen_address
         .parse()
         .map_err(|x: std::net::AddrParseError| Error::Config(x.to_string()))?;
 
-    let (addr, server) =
-        warp::serve(filter).bind_with_graceful_shutdown(address, cancel.cancelled_owned());
+    let (addr, server) = warp::serve(filter)
+        .try_bind_with_graceful_shutdown(address, cancel.cancelled_owned())
+        .map_err(|x| Error::Config(format!("can't listen on {address}: {x}")))?;
 
     tracing::info!(%addr, "Json-RPC server listening");
 
@@ -196,16 +438,223 @@ pub async fn serve(config: Config, cancel: CancellationToken) -> Result<(), Erro
     Ok(())
 }
 
+/// Serves the TRP JSON-RPC API.
+#[derive(clap::Parser)]
+struct Cli {
+    /// Path of the TOML config file. Defaults to `trp.toml` when it exists.
+    #[arg(short, long, env = "TRP_CONFIG")]
+    config: Option<PathBuf>,
+}
+
 #[tokio::main]
 async fn main() {
-    let config = Config {
-        listen_address: "0.0.0.0:8000".to_string(),
-        ledger: tx3_cardano::ledgers::u5c::Config {
-            endpoint_url: "https://mainnet.utxorpc-v0.demeter.run".to_string(),
-            api_key: "dmtr_utxorpc1wgnnj0qcfj32zxsz2uc8d4g7uclm2s2w".to_string(),
-            network_id: 1,
-        },
+    let cli = Cli::parse();
+
+    let result = match Config::load(cli.config.as_deref()) {
+        Ok(config) => serve(config, CancellationToken::new()).await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tx3_cardano::{
+        ledgers::{emulator::EmulatorLedger, mock::MockLedger},
+        pallas::ledger::addresses::Address,
     };
+    use tx3_lang::{ArgValue, Protocol};
+    use tx3_trp::TrpClient;
+
+    use super::*;
+
+    const ADDRESS: &str = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+    /// The emulator runs with testnet params, so it only accepts testnet
+    /// addresses.
+    const TESTNET_ADDRESS: &str = "addr_test1qz0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6qllhhm4";
+
+    /// Serves the API on a random local port, backed by the mock ledger.
+    fn spawn_server() -> String {
+        spawn_server_with(AnyLedger::Mock(MockLedger))
+    }
+
+    fn spawn_server_with(ledger: AnyLedger) -> String {
+        spawn_context(Context {
+            ledger,
+            registry: Default::default(),
+        })
+    }
+
+    fn spawn_context(context: Context) -> String {
+        let (addr, server) = warp::serve(routes(context)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        format!("http://{addr}")
+    }
+
+    fn transfer() -> tx3_lang::ProtoTx {
+        let code = concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples/transfer.tx3");
+        let protocol = Protocol::from_file(code).load().unwrap();
+        protocol.new_tx("transfer").unwrap()
+    }
 
-    serve(config, CancellationToken::new()).await.unwrap();
+    #[tokio::test]
+    async fn client_resolves_tx() {
+        let address = Address::from_bech32(ADDRESS).unwrap().to_vec();
+
+        // args can be set on the tx or passed along with it
+        let tx = transfer().with_arg("Receiver", ArgValue::Address(address.clone()));
+
+        let args = BTreeMap::from([
+            ("sender".to_string(), ArgValue::Address(address)),
+            ("quantity".to_string(), ArgValue::Int(10_000_000)),
+        ]);
+
+        let endpoint = spawn_server();
+
+        for encoding in [Encoding::Hex, Encoding::Base64, Encoding::Json] {
+            let client = TrpClient::new(&endpoint).with_encoding(encoding);
+            let cbor = client.resolve(&tx, &args).await.unwrap();
+            assert!(!cbor.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_params() {
+        let client = TrpClient::new(spawn_server());
+        let tir = IrEnvelope::new(&transfer(), Encoding::Hex);
+
+        let result: serde_json::Value = client
+            .call("trp.params", json!({ "tir": tir }))
+            .await
+            .unwrap();
+
+        let params = result["params"].as_object().unwrap();
+        assert!(params.contains_key("quantity"));
+        assert!(params.contains_key("receiver"));
+        assert!(params.contains_key("sender"));
+        assert!(!result["queries"].as_object().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn serves_params_of_templates() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples");
+
+        let endpoint = spawn_context(Context {
+            ledger: AnyLedger::Mock(MockLedger),
+            registry: Arc::new(Registry::load(examples).unwrap()),
+        });
+
+        let client = TrpClient::new(endpoint);
+
+        let result: serde_json::Value = client
+            .call(
+                "trp.params",
+                json!({ "protocol": "transfer", "template": "transfer" }),
+            )
+            .await
+            .unwrap();
+
+        let params = result["params"].as_object().unwrap();
+        assert!(params.contains_key("quantity"));
+        assert!(params.contains_key("receiver"));
+        assert!(params.contains_key("sender"));
+
+        let err = client
+            .call::<_, serde_json::Value>(
+                "trp.params",
+                json!({ "protocol": "transfer", "template": "missing" }),
+            )
+            .await;
+
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn evaluates_tx() {
+        let client = TrpClient::new(spawn_server());
+        let tir = IrEnvelope::new(&transfer(), Encoding::Hex);
+
+        let args = json!({
+            "sender": ADDRESS,
+            "receiver": ADDRESS,
+            "quantity": 10_000_000,
+        });
+
+        let result: serde_json::Value = client
+            .call("trp.evaluate", json!({ "tir": tir, "args": args }))
+            .await
+            .unwrap();
+
+        assert!(result["fee"].as_u64().unwrap() > 0);
+        assert!(result["size"].as_u64().unwrap() > 0);
+        assert_eq!(result.get("exUnits").is_some(), cfg!(feature = "phase2"));
+    }
+
+    #[tokio::test]
+    async fn submits_tx() {
+        let address = Address::from_bech32(TESTNET_ADDRESS).unwrap().to_vec();
+
+        let ledger = EmulatorLedger::default();
+        ledger.fund(&address, 500_000_000);
+
+        let client = TrpClient::new(spawn_server_with(AnyLedger::Emulator(ledger.clone())));
+
+        let args = BTreeMap::from([
+            ("sender".to_string(), ArgValue::Address(address.clone())),
+            ("receiver".to_string(), ArgValue::Address(address)),
+            ("quantity".to_string(), ArgValue::Int(10_000_000)),
+        ]);
+
+        let cbor = client.resolve(&transfer(), &args).await.unwrap();
+
+        let tx = TxEnvelope {
+            version: None,
+            payload: hex::encode(&cbor),
+            encoding: Encoding::Hex,
+        };
+
+        let result: serde_json::Value = client
+            .call("trp.submit", json!({ "tx": tx }))
+            .await
+            .unwrap();
+
+        let hash = hex::decode(result["hash"].as_str().unwrap()).unwrap();
+        assert!(ledger.utxos().iter().any(|x| x.r#ref.txid == hash));
+
+        // the inputs are spent by now, so submitting it again is rejected
+        let err = client
+            .call::<_, serde_json::Value>("trp.submit", json!({ "tx": tx }))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, tx3_trp::Error::Submit(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn client_maps_server_errors() {
+        let client = TrpClient::new(spawn_server());
+
+        let args = BTreeMap::from([("quantity".to_string(), ArgValue::Int(10_000_000))]);
+
+        let err = client.resolve(&transfer(), &args).await.unwrap_err();
+        assert!(matches!(err, tx3_trp::Error::MissingArg(arg) if arg == "receiver"));
+
+        let args = BTreeMap::from([("quantity".to_string(), ArgValue::Bool(true))]);
+
+        let err = client.resolve(&transfer(), &args).await.unwrap_err();
+        assert!(matches!(err, tx3_trp::Error::InvalidArg { arg, .. } if arg == "quantity"));
+
+        let err = client
+            .call::<_, serde_json::Value>("trp.unknown", json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, tx3_trp::Error::UnknownMethod(x) if x == "trp.unknown"));
+    }
 }
@@ -0,0 +1,307 @@
+//! Registry of the protocols served by TRP.
+//!
+//! Protocols are loaded from a directory of `.tx3` files, keyed by the stem of
+//! each file. An `.env` file with the same stem, when present, provides the
+//! env args of the protocol. The directory is polled for changes so that
+//! protocols can be edited without restarting the server.
+//!
+//! The AST of a protocol can't be shared across threads, so each protocol is
+//! kept as the lowered tx of each of its templates, along with the types of
+//! its params.
+
+use std::{
+    collections::BTreeMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use tx3_lang::{ProtoTx, Protocol};
+
+use crate::{args::Schema, Error};
+
+struct Entry {
+    txs: BTreeMap<String, (ProtoTx, Schema)>,
+    fingerprint: u64,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    dir: Option<PathBuf>,
+    protocols: RwLock<BTreeMap<String, Entry>>,
+}
+
+/// Hashes the contents of the protocol file along with its env file, so that
+/// changes are noticed regardless of the mtime resolution of the filesystem.
+fn fingerprint(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    for file in [path.to_path_buf(), path.with_extension("env")] {
+        std::fs::read(file).ok().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+fn load_protocol(path: &Path) -> Result<BTreeMap<String, (ProtoTx, Schema)>, Error> {
+    let mut loader = Protocol::from_file(path);
+
+    let env_file = path.with_extension("env");
+
+    if env_file.exists() {
+        loader = loader.with_env_file(env_file);
+    }
+
+    let protocol = loader
+        .load()
+        .map_err(|err| Error::InvalidProtocol(path.display().to_string(), err.to_string()))?;
+
+    protocol
+        .txs()
+        .map(|def| {
+            let tx = protocol
+                .new_tx(&def.name)
+                .map_err(|err| Error::InvalidTemplate(def.name.clone(), err.to_string()))?;
+
+            let schema = Schema::from_protocol(&protocol, &def.name, &tx);
+
+            Ok((def.name.clone(), (tx, schema)))
+        })
+        .collect()
+}
+
+/// Lists the `.tx3` files of the dir, keyed by protocol name.
+fn scan_dir(dir: &Path) -> Result<BTreeMap<String, PathBuf>, Error> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|err| Error::Config(format!("can't read {}: {err}", dir.display())))?;
+
+    let files = entries
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.extension().is_some_and(|ext| ext == "tx3"))
+        .filter_map(|x| Some((x.file_stem()?.to_str()?.to_string(), x)));
+
+    Ok(files.collect())
+}
+
+impl Registry {
+    /// Loads all the protocols of the dir. Protocols that fail to load are
+    /// skipped, so that a single broken file doesn't take the server down.
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let registry = Self {
+            dir: Some(dir.into()),
+            protocols: Default::default(),
+        };
+
+        registry.refresh()?;
+
+        Ok(registry)
+    }
+
+    /// Reloads the protocols whose files changed since the last time they
+    /// were loaded and drops the ones whose files were removed. A protocol
+    /// that fails to reload keeps its previous version.
+    pub fn refresh(&self) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let files = scan_dir(dir)?;
+
+        let loaded: BTreeMap<_, _> = self
+            .protocols
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.fingerprint))
+            .collect();
+
+        // parsing is slow, so it's done before taking the lock to keep
+        // requests from waiting on it
+        let mut changed = Vec::new();
+
+        for (name, path) in &files {
+            let fingerprint = fingerprint(path);
+
+            if loaded.get(name) == Some(&fingerprint) {
+                continue;
+            }
+
+            match load_protocol(path) {
+                Ok(txs) => {
+                    info!(name, "protocol loaded");
+                    changed.push((name.clone(), Entry { txs, fingerprint }));
+                }
+                Err(err) => warn!(name, %err, "can't load protocol"),
+            }
+        }
+
+        let mut protocols = self.protocols.write().unwrap();
+
+        protocols.extend(changed);
+
+        protocols.retain(|name, _| {
+            let keep = files.contains_key(name);
+
+            if !keep {
+                info!(name, "protocol removed");
+            }
+
+            keep
+        });
+
+        Ok(())
+    }
+
+    /// Polls the dir for changes every `interval` until cancelled.
+    pub async fn watch(self: Arc<Self>, interval: Duration, cancel: CancellationToken) {
+        let mut ticks = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    let registry = self.clone();
+
+                    // the error may hold AST nodes, which can't be sent
+                    // across threads
+                    let refresh = move || registry.refresh().map_err(|x| x.to_string());
+
+                    match tokio::task::spawn_blocking(refresh).await {
+                        Ok(Ok(())) => (),
+                        Ok(Err(err)) => warn!(%err, "can't refresh protocols"),
+                        Err(err) => warn!(%err, "refresh of protocols panicked"),
+                    }
+                }
+                _ = cancel.cancelled() => break,
+            }
+        }
+    }
+
+    /// Lists the loaded protocols along with the names of their txs.
+    pub fn list(&self) -> Value {
+        let protocols = self.protocols.read().unwrap();
+
+        let list: Vec<_> = protocols
+            .iter()
+            .map(|(name, entry)| json!({ "name": name, "txs": entry.txs.keys().collect::<Vec<_>>() }))
+            .collect();
+
+        json!(list)
+    }
+
+    /// Describes the txs of a protocol with the params and input queries of
+    /// each of them.
+    pub fn describe(&self, name: &str) -> Result<Value, Error> {
+        let protocols = self.protocols.read().unwrap();
+
+        let entry = protocols
+            .get(name)
+            .ok_or_else(|| Error::UnknownProtocol(name.to_string()))?;
+
+        let txs: Vec<_> = entry
+            .txs
+            .iter()
+            .map(|(template, (tx, _))| {
+                json!({
+                    "name": template,
+                    "params": tx.find_params(),
+                    "queries": tx.find_queries(),
+                })
+            })
+            .collect();
+
+        Ok(json!({ "name": name, "txs": txs }))
+    }
+
+    /// Returns the lowered tx of a template of a protocol, along with the
+    /// types of its params.
+    pub fn new_tx(&self, protocol: &str, template: &str) -> Result<(ProtoTx, Schema), Error> {
+        let protocols = self.protocols.read().unwrap();
+
+        let entry = protocols
+            .get(protocol)
+            .ok_or_else(|| Error::UnknownProtocol(protocol.to_string()))?;
+
+        entry
+            .txs
+            .get(template)
+            .cloned()
+            .ok_or_else(|| Error::UnknownTemplate(template.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn examples_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../examples")
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tx3-registry-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn serves_protocols_of_dir() {
+        let dir = temp_dir("serve");
+        std::fs::copy(
+            examples_dir().join("transfer.tx3"),
+            dir.join("transfer.tx3"),
+        )
+        .unwrap();
+
+        let registry = Registry::load(&dir).unwrap();
+
+        assert_eq!(
+            registry.list(),
+            json!([{ "name": "transfer", "txs": ["transfer"] }])
+        );
+
+        let description = registry.describe("transfer").unwrap();
+        assert_eq!(description["txs"][0]["params"]["quantity"], json!("Int"));
+
+        assert!(registry.new_tx("transfer", "transfer").is_ok());
+
+        assert!(matches!(
+            registry.new_tx("transfer", "other"),
+            Err(Error::UnknownTemplate(_))
+        ));
+
+        assert!(matches!(
+            registry.new_tx("other", "transfer"),
+            Err(Error::UnknownProtocol(_))
+        ));
+    }
+
+    #[test]
+    fn reloads_changed_files() {
+        let dir = temp_dir("reload");
+        std::fs::copy(examples_dir().join("transfer.tx3"), dir.join("p.tx3")).unwrap();
+
+        let registry = Registry::load(&dir).unwrap();
+        assert!(registry.new_tx("p", "transfer").is_ok());
+
+        std::fs::copy(examples_dir().join("vesting.tx3"), dir.join("p.tx3")).unwrap();
+        std::fs::copy(examples_dir().join("faucet.tx3"), dir.join("q.tx3")).unwrap();
+
+        registry.refresh().unwrap();
+        assert!(registry.new_tx("p", "lock").is_ok());
+        assert!(registry.new_tx("q", "claim_with_password").is_ok());
+
+        std::fs::remove_file(dir.join("q.tx3")).unwrap();
+
+        registry.refresh().unwrap();
+        assert!(matches!(
+            registry.new_tx("q", "claim_with_password"),
+            Err(Error::UnknownProtocol(_))
+        ));
+    }
+}
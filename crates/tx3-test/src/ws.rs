@@ -0,0 +1,176 @@
+//! WebSocket transport of the JSON-RPC API.
+//!
+//! Each text message is handled as a JSON-RPC body, the same way as the ones
+//! posted over HTTP, and gets its response as another text message. Requests
+//! are handled concurrently, so responses may arrive out of order.
+//!
+//! While a tx is resolved, the server pushes `trp.progress` notifications
+//! with the steps of the resolution (ledger queries, selected UTxOs, fee
+//! rounds, compilation), carrying the id of the request they belong to.
+
+use futures_util::{SinkExt as _, StreamExt as _};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::debug;
+use tx3_cardano::ResolveEvent;
+use warp::ws::{Message, WebSocket};
+
+use crate::{
+    jsonrpc::{Id, Notification},
+    Context,
+};
+
+/// Reports the steps of resolving the tx of a request. It does nothing for
+/// clients that can't receive notifications, like the HTTP ones.
+#[derive(Clone, Default)]
+pub struct Progress {
+    sink: Option<mpsc::UnboundedSender<Value>>,
+    id: Option<Id>,
+}
+
+impl Progress {
+    fn new(sink: mpsc::UnboundedSender<Value>) -> Self {
+        Self {
+            sink: Some(sink),
+            id: None,
+        }
+    }
+
+    /// Binds the progress to a request. Notifications (requests without an
+    /// id) don't get progress, as it can't be matched to them.
+    pub fn for_request(&self, id: Option<Id>) -> Self {
+        Self {
+            sink: id.as_ref().and(self.sink.clone()),
+            id,
+        }
+    }
+
+    pub fn report(&self, event: ResolveEvent) {
+        let (Some(sink), Some(id)) = (&self.sink, &self.id) else {
+            return;
+        };
+
+        let notification = Notification::new("trp.progress", json!({ "id": id, "event": event }));
+
+        // the socket is gone, there's no one left to report to
+        let _ = sink.send(json!(notification));
+    }
+}
+
+pub async fn handle_socket(context: Context, socket: WebSocket) {
+    let (mut tx, mut rx) = socket.split();
+
+    let (sink, mut outgoing) = mpsc::unbounded_channel::<Value>();
+
+    let forward = tokio::spawn(async move {
+        while let Some(value) = outgoing.recv().await {
+            if tx.send(Message::text(value.to_string())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = rx.next().await {
+        if message.is_close() {
+            break;
+        }
+
+        // pings are answered by warp and there's nothing to do for binary
+        // messages
+        let Ok(text) = message.to_str() else {
+            continue;
+        };
+
+        let body = text.as_bytes().to_vec();
+        let context = context.clone();
+        let sink = sink.clone();
+
+        tokio::spawn(async move {
+            let progress = Progress::new(sink.clone());
+
+            if let Some(reply) = crate::handle_body(context, &body, &progress).await {
+                let _ = sink.send(reply);
+            }
+        });
+    }
+
+    debug!("websocket closed");
+
+    // the forward ends once the in-flight requests drop their sinks
+    drop(sink);
+    let _ = forward.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tx3_cardano::{ledgers::emulator::EmulatorLedger, pallas::ledger::addresses::Address};
+
+    use super::*;
+    use crate::{ledger::AnyLedger, registry::Registry};
+
+    const ADDRESS: &str = "addr1qx0rs5qrvx9qkndwu0w88t0xghgy3f53ha76kpx8uf496m9rn2ursdm3r0fgf5pmm4lpufshl8lquk5yykg4pd00hp6quf2hh2";
+
+    fn context() -> Context {
+        let ledger = EmulatorLedger::default();
+        let address = Address::from_bech32(ADDRESS).unwrap();
+        ledger.fund(&address.to_vec(), 1_000_000_000);
+
+        let dir = std::env::temp_dir().join(format!("tx3-ws-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples");
+        std::fs::copy(examples.join("transfer.tx3"), dir.join("transfer.tx3")).unwrap();
+
+        Context {
+            ledger: AnyLedger::Emulator(ledger),
+            registry: Arc::new(Registry::load(&dir).unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_resolve_progress() {
+        let mut client = warp::test::ws()
+            .path("/ws")
+            .handshake(crate::routes(context()))
+            .await
+            .unwrap();
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "trp.resolve",
+            "params": {
+                "protocol": "transfer",
+                "template": "transfer",
+                "args": { "sender": ADDRESS, "receiver": ADDRESS, "quantity": 10_000_000 },
+            },
+        });
+
+        client.send_text(request.to_string()).await;
+
+        let mut events = vec![];
+
+        let response = loop {
+            let message = client.recv().await.unwrap();
+            let value: Value = serde_json::from_str(message.to_str().unwrap()).unwrap();
+
+            if value["method"] == "trp.progress" {
+                assert_eq!(value["params"]["id"], json!(7));
+                events.push(value["params"]["event"]["type"].clone());
+            } else {
+                break value;
+            }
+        };
+
+        assert_eq!(response["id"], json!(7));
+        assert!(response["result"]["tx"].is_string());
+
+        assert_eq!(events[0], json!("queryIssued"));
+        assert_eq!(events[1], json!("inputsSelected"));
+        assert!(events.contains(&json!("feeRound")));
+        assert_eq!(events.last(), Some(&json!("compiled")));
+    }
+}
@@ -0,0 +1,106 @@
+//! Encoding of args into the JSON shapes the server decodes them from: ints
+//! as numbers (or decimal strings when they don't fit), bytes and addresses
+//! as hex, utxo refs as `<txid hex>#<index>` and custom types as objects with
+//! their `constructor` index and `fields`.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+use tx3_lang::ArgValue;
+
+use crate::Error;
+
+fn encode_arg(value: &ArgValue) -> Result<Value, String> {
+    let value = match value {
+        // ints that don't fit in a JSON number go as decimal strings
+        ArgValue::Int(x) => match i64::try_from(*x) {
+            Ok(x) => json!(x),
+            Err(_) => json!(x.to_string()),
+        },
+        ArgValue::Bool(x) => json!(x),
+        ArgValue::String(x) => json!(x),
+        ArgValue::Bytes(x) | ArgValue::Address(x) => json!(hex::encode(x)),
+        ArgValue::UtxoRef(x) => json!(format!("{}#{}", hex::encode(&x.txid), x.index)),
+        ArgValue::List(items) => items
+            .iter()
+            .map(encode_arg)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array)?,
+        ArgValue::Struct {
+            constructor,
+            fields,
+        } => {
+            let fields = fields
+                .iter()
+                .map(encode_arg)
+                .collect::<Result<Vec<_>, _>>()?;
+            json!({ "constructor": constructor, "fields": fields })
+        }
+        ArgValue::UtxoSet(_) => return Err("utxo sets can't be sent as args".to_string()),
+    };
+
+    Ok(value)
+}
+
+/// Encodes the args of a request as a JSON object keyed by param name.
+pub fn encode_args(args: &BTreeMap<String, ArgValue>) -> Result<Value, Error> {
+    let mut out = Map::new();
+
+    for (key, value) in args {
+        let value = encode_arg(value).map_err(|err| Error::UnsupportedArg(key.clone(), err))?;
+        out.insert(key.clone(), value);
+    }
+
+    Ok(Value::Object(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use tx3_lang::UtxoRef;
+
+    use super::*;
+
+    #[test]
+    fn encodes_each_shape() {
+        let args = BTreeMap::from([
+            ("quantity".to_string(), ArgValue::Int(10)),
+            ("big".to_string(), ArgValue::Int(i128::MAX)),
+            ("password".to_string(), ArgValue::Bytes(vec![0xab, 0xcd])),
+            (
+                "locked".to_string(),
+                ArgValue::UtxoRef(UtxoRef {
+                    txid: vec![1; 32],
+                    index: 2,
+                }),
+            ),
+            (
+                "state".to_string(),
+                ArgValue::Struct {
+                    constructor: 1,
+                    fields: vec![ArgValue::Bool(true), ArgValue::List(vec![])],
+                },
+            ),
+        ]);
+
+        let json = encode_args(&args).unwrap();
+
+        assert_eq!(json["quantity"], json!(10));
+        assert_eq!(json["big"], json!(i128::MAX.to_string()));
+        assert_eq!(json["password"], json!("abcd"));
+        assert_eq!(json["locked"], json!(format!("{}#2", "01".repeat(32))));
+        assert_eq!(
+            json["state"],
+            json!({ "constructor": 1, "fields": [true, []] })
+        );
+    }
+
+    #[test]
+    fn rejects_utxo_sets() {
+        let args = BTreeMap::from([("x".to_string(), ArgValue::UtxoSet(Default::default()))]);
+
+        assert!(matches!(
+            encode_args(&args),
+            Err(Error::UnsupportedArg(arg, _)) if arg == "x"
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+//! Client for TRP, the JSON-RPC API that resolves tx3 txs against a ledger.
+//!
+//! ```no_run
+//! # async fn example(tx: tx3_lang::ProtoTx) -> Result<(), tx3_trp::Error> {
+//! use std::collections::BTreeMap;
+//! use tx3_lang::ArgValue;
+//! use tx3_trp::TrpClient;
+//!
+//! let client = TrpClient::new("http://localhost:8000");
+//!
+//! let args = BTreeMap::from([("quantity".to_string(), ArgValue::Int(10_000_000))]);
+//! let cbor = client.resolve(&tx, &args).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Errors reported by the server are mapped into the variants of [`Error`]
+//! according to their code and data.
+
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tx3_lang::{ir::InputQuery, ArgValue, ProtoTx};
+
+pub mod args;
+pub mod spec;
+
+use spec::{Encoding, IrEnvelope, ResolveParams, ResolveResult, TxSource};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("can't decode response: {0}")]
+    InvalidResponse(String),
+
+    #[error("can't apply the args of the tx: {0}")]
+    Apply(#[from] tx3_lang::applying::Error),
+
+    #[error("arg {0} can't be sent: {1}")]
+    UnsupportedArg(String, String),
+
+    #[error("invalid arg value {arg}: {reason}")]
+    InvalidArg { arg: String, reason: String },
+
+    #[error("missing arg {0}")]
+    MissingArg(String),
+
+    #[error(
+        "IR version '{version}' is not supported by the server, expected one of {supported:?}"
+    )]
+    UnsupportedVersion {
+        version: String,
+        supported: Vec<String>,
+    },
+
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+
+    #[error("unknown method: {0}")]
+    UnknownMethod(String),
+
+    #[error("input {input} not resolved")]
+    InputsNotResolved {
+        input: String,
+        query: Option<Box<InputQuery>>,
+    },
+
+    #[error("resolve error: {0}")]
+    Resolve(String),
+
+    #[error("submit error: {0}")]
+    Submit(String),
+
+    #[error("server error {code}: {message}")]
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<ErrorObject>,
+}
+
+fn data_str(data: &Option<Value>, key: &str) -> Option<String> {
+    data.as_ref()?.get(key)?.as_str().map(str::to_string)
+}
+
+impl From<ErrorObject> for Error {
+    fn from(err: ErrorObject) -> Self {
+        let ErrorObject {
+            code,
+            message,
+            data,
+        } = err;
+
+        match code {
+            spec::INVALID_PARAMS => {
+                let arg = data_str(&data, "arg");
+                let reason = data_str(&data, "reason");
+                let version = data_str(&data, "version");
+
+                match (arg, reason, version) {
+                    (Some(arg), Some(reason), _) => Error::InvalidArg { arg, reason },
+                    (Some(arg), None, _) => Error::MissingArg(arg),
+                    (_, _, Some(version)) => {
+                        let supported = data
+                            .as_ref()
+                            .and_then(|x| x.get("supported"))
+                            .and_then(|x| serde_json::from_value(x.clone()).ok())
+                            .unwrap_or_default();
+
+                        Error::UnsupportedVersion { version, supported }
+                    }
+                    _ => Error::InvalidParams(message),
+                }
+            }
+            spec::METHOD_NOT_FOUND => {
+                Error::UnknownMethod(data_str(&data, "method").unwrap_or(message))
+            }
+            spec::INPUTS_NOT_RESOLVED => match data_str(&data, "input") {
+                Some(input) => {
+                    let query = data
+                        .as_ref()
+                        .and_then(|x| x.get("query"))
+                        .and_then(|x| serde_json::from_value(x.clone()).ok());
+
+                    Error::InputsNotResolved { input, query }
+                }
+                None => Error::Rpc {
+                    code,
+                    message,
+                    data,
+                },
+            },
+            spec::RESOLVE_ERROR => Error::Resolve(message),
+            spec::SUBMIT_ERROR => Error::Submit(message),
+            _ => Error::Rpc {
+                code,
+                message,
+                data,
+            },
+        }
+    }
+}
+
+/// A client of a TRP server.
+pub struct TrpClient {
+    endpoint: String,
+    encoding: Encoding,
+    headers: reqwest::header::HeaderMap,
+    http: reqwest::Client,
+    next_id: AtomicU64,
+}
+
+impl TrpClient {
+    /// Creates a client of the server at the given URL, sending TIR as hex.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            encoding: Encoding::Hex,
+            headers: Default::default(),
+            http: Default::default(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the encoding of the TIR sent to the server.
+    pub fn with_encoding(self, encoding: Encoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// Sets headers sent along with every request, such as API keys.
+    pub fn with_headers(self, headers: reqwest::header::HeaderMap) -> Self {
+        Self { headers, ..self }
+    }
+
+    /// Calls a method of the server, decoding its result.
+    pub async fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let response: Response = self
+            .http
+            .post(&self.endpoint)
+            .headers(self.headers.clone())
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match response {
+            Response {
+                error: Some(err), ..
+            } => Err(err.into()),
+            Response {
+                result: Some(result),
+                ..
+            } => serde_json::from_value(result).map_err(|x| Error::InvalidResponse(x.to_string())),
+            _ => Err(Error::InvalidResponse(
+                "neither a result nor an error".to_string(),
+            )),
+        }
+    }
+
+    /// Resolves the tx with the given args, returning the CBOR of the
+    /// resolved tx. Args already set on the tx are applied before sending it.
+    pub async fn resolve(
+        &self,
+        tx: &ProtoTx,
+        args: &BTreeMap<String, ArgValue>,
+    ) -> Result<Vec<u8>, Error> {
+        let tx = tx.clone().apply()?;
+
+        let params = ResolveParams {
+            source: TxSource::Tir {
+                tir: IrEnvelope::new(&tx, self.encoding),
+            },
+            args: args::encode_args(args)?,
+        };
+
+        let result: ResolveResult = self.call("trp.resolve", params).await?;
+
+        hex::decode(result.tx).map_err(|x| Error::InvalidResponse(x.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(code: i64, data: Value) -> Error {
+        ErrorObject {
+            code,
+            message: "message".to_string(),
+            data: Some(data),
+        }
+        .into()
+    }
+
+    #[test]
+    fn maps_server_errors() {
+        assert!(matches!(
+            error(spec::INVALID_PARAMS, json!({ "arg": "quantity", "reason": "nope" })),
+            Error::InvalidArg { arg, .. } if arg == "quantity"
+        ));
+
+        assert!(matches!(
+            error(spec::INVALID_PARAMS, json!({ "arg": "quantity" })),
+            Error::MissingArg(arg) if arg == "quantity"
+        ));
+
+        assert!(matches!(
+            error(spec::INVALID_PARAMS, json!({ "version": "v1alpha3", "supported": ["v1alpha6"] })),
+            Error::UnsupportedVersion { version, supported } if version == "v1alpha3" && supported == ["v1alpha6"]
+        ));
+
+        assert!(matches!(
+            error(spec::INPUTS_NOT_RESOLVED, json!({ "input": "source", "query": null })),
+            Error::InputsNotResolved { input, query: None } if input == "source"
+        ));
+
+        assert!(matches!(
+            error(-32099, json!({})),
+            Error::Rpc { code: -32099, .. }
+        ));
+    }
+}
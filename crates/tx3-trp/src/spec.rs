@@ -0,0 +1,100 @@
+//! Params, results and error codes of the TRP methods, shared by the server
+//! and its clients.
+
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tx3_lang::{ir::IR_VERSION, ProtoTx};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// Error codes reserved for the server, in the `-32000` to `-32099` range.
+pub const RESOLVE_ERROR: i64 = -32000;
+pub const INPUTS_NOT_RESOLVED: i64 = -32001;
+pub const SUBMIT_ERROR: i64 = -32002;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    #[serde(rename = "base64")]
+    Base64,
+    #[serde(rename = "hex")]
+    Hex,
+    /// The JSON encoding of the IR, only valid for TIR.
+    #[serde(rename = "json")]
+    Json,
+}
+
+impl Encoding {
+    pub fn decode(&self, data: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|x| x.to_string()),
+            Encoding::Hex => hex::decode(data).map_err(|x| x.to_string()),
+            Encoding::Json => Err("json encoding is only supported for tir".to_string()),
+        }
+    }
+}
+
+/// The IR of a tx along with the version of the IR it was encoded with.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IrEnvelope {
+    pub version: String,
+
+    pub bytecode: String,
+    pub encoding: Encoding,
+}
+
+impl IrEnvelope {
+    /// Encodes the IR of the tx with the current IR version.
+    pub fn new(tx: &ProtoTx, encoding: Encoding) -> Self {
+        let bytecode = match encoding {
+            Encoding::Base64 => base64::engine::general_purpose::STANDARD.encode(tx.ir_bytes()),
+            Encoding::Hex => hex::encode(tx.ir_bytes()),
+            Encoding::Json => tx.to_ir_json(),
+        };
+
+        Self {
+            version: IR_VERSION.to_string(),
+            bytecode,
+            encoding,
+        }
+    }
+}
+
+/// The CBOR of a tx.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TxEnvelope {
+    pub version: Option<String>,
+
+    pub payload: String,
+    pub encoding: Encoding,
+}
+
+/// The tx to resolve, either as TIR or as a template of a protocol of the
+/// registry of the server.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum TxSource {
+    Tir { tir: IrEnvelope },
+    Template { protocol: String, template: String },
+}
+
+/// Params of `trp.resolve` and `trp.evaluate`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResolveParams {
+    #[serde(flatten)]
+    pub source: TxSource,
+    #[serde(default)]
+    pub args: Value,
+}
+
+/// Result of `trp.resolve`, with the hex of the CBOR of the resolved tx.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResolveResult {
+    pub tx: String,
+}